@@ -0,0 +1,151 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::date::DateTime;
+
+/// Format of the access log line emitted for every handled request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// Common log format followed by the handling time:
+    /// `127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /index.html HTTP/1.1" 200 2326 12ms`
+    Common,
+    /// One JSON object per line:
+    /// `{"ts":...,"ip":...,"method":...,"path":...,"status":...,"bytes":...,"duration_ms":...}`,
+    /// ts being milliseconds since the unix epoch and ip null if the client address is unknown
+    Json,
+}
+
+/// Everything known about a request once its response has been written
+pub(crate) struct AccessLogEntry<'a> {
+    /// None if the peer address could not be determined
+    pub peer: Option<SocketAddr>,
+    pub method: &'a str,
+    pub path: &'a str,
+    /// Protocol as in the request line, e.g. `HTTP/1.1`
+    pub protocol: &'a str,
+    pub status: u16,
+    pub bytes: u64,
+    pub duration: Duration,
+}
+
+impl AccessLogEntry<'_> {
+    /// Renders the entry as a single log line
+    pub(crate) fn format(&self, format: AccessLogFormat) -> String {
+        match format {
+            AccessLogFormat::Common => format!(
+                "{} - - [{}] \"{} {} {}\" {} {} {}ms",
+                self.peer
+                    .map_or_else(|| String::from("-"), |peer| peer.ip().to_string()),
+                DateTime::from_system_time(SystemTime::now()).to_common_log(),
+                self.method,
+                self.path,
+                self.protocol,
+                self.status,
+                self.bytes,
+                self.duration.as_millis()
+            ),
+            AccessLogFormat::Json => {
+                let ts = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+                format!(
+                    r#"{{"ts":{ts},"ip":{},"method":"{}","path":"{}","status":{},"bytes":{},"duration_ms":{}}}"#,
+                    self.peer
+                        .map_or_else(|| String::from("null"), |peer| format!("\"{}\"", peer.ip())),
+                    escape_json(self.method),
+                    escape_json(self.path),
+                    self.status,
+                    self.bytes,
+                    self.duration.as_millis()
+                )
+            }
+        }
+    }
+}
+
+/// Escapes a string for use inside a JSON string literal
+fn escape_json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> AccessLogEntry<'static> {
+        AccessLogEntry {
+            peer: Some(SocketAddr::from(([192, 168, 0, 7], 51234))),
+            method: "GET",
+            path: "/search?q=\"quoted\"\n",
+            protocol: "HTTP/1.1",
+            status: 200,
+            bytes: 2326,
+            duration: Duration::from_millis(12),
+        }
+    }
+
+    #[test]
+    fn json_line_parses_with_documented_keys() {
+        let line = entry().format(AccessLogFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        let object = value.as_object().unwrap();
+        let mut keys: Vec<&str> = object.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            [
+                "bytes",
+                "duration_ms",
+                "ip",
+                "method",
+                "path",
+                "status",
+                "ts"
+            ]
+        );
+        assert!(object["ts"].as_u64().is_some());
+        assert_eq!(object["ip"], "192.168.0.7");
+        assert_eq!(object["method"], "GET");
+        assert_eq!(object["path"], "/search?q=\"quoted\"\n");
+        assert_eq!(object["status"], 200);
+        assert_eq!(object["bytes"], 2326);
+        assert_eq!(object["duration_ms"], 12);
+    }
+
+    #[test]
+    fn common_line_has_the_request_line() {
+        let entry = AccessLogEntry {
+            path: "/index.html",
+            ..entry()
+        };
+        let line = entry.format(AccessLogFormat::Common);
+        assert!(line.starts_with("192.168.0.7 - - ["));
+        assert!(line.ends_with("] \"GET /index.html HTTP/1.1\" 200 2326 12ms"));
+    }
+
+    #[test]
+    fn json_line_without_peer_has_null_ip() {
+        let entry = AccessLogEntry {
+            peer: None,
+            ..entry()
+        };
+        let value: serde_json::Value =
+            serde_json::from_str(&entry.format(AccessLogFormat::Json)).unwrap();
+        assert!(value["ip"].is_null());
+    }
+}
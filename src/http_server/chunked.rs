@@ -0,0 +1,121 @@
+use std::io::{self, BufRead, Read, Write};
+
+use super::{
+    head::{DEFAULT_MAX_HEADERS, DEFAULT_MAX_LINE_LENGTH},
+    parse::{read_headers, read_line},
+};
+
+/// Writes everything written to it as chunks of the chunked transfer coding, used for streamed
+/// bodies of unknown length. Every write becomes one chunk so streamed data is sent as it arrives
+pub(crate) struct ChunkedWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    /// Writes the last chunk that ends the body
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.inner.write_all(b"0\r\n\r\n")?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // an empty chunk would end the body early
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.inner.write_all(format!("{:x}\r\n", buf.len()).as_bytes())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decodes a body in the chunked transfer coding while it is read, used for request bodies that
+/// are streamed to the handler. Reads return 0 after the last chunk, the trailer fields following
+/// it are kept in trailers. Chunk size and trailer lines are bounded and trailers are parsed as
+/// the header lines of a head by default
+pub(crate) struct ChunkedReader<R: BufRead> {
+    inner: R,
+    /// Bytes left in the current chunk
+    remaining: u64,
+    done: bool,
+    trailers: Vec<(String, String)>,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            done: false,
+            trailers: Vec::new(),
+        }
+    }
+
+    /// Returns the trailer fields, which are only read once the last chunk has been reached
+    pub(crate) fn into_trailers(self) -> Vec<(String, String)> {
+        self.trailers
+    }
+
+    /// Reads the trailer fields up to the blank line ending the body
+    fn read_trailers(&mut self) -> io::Result<()> {
+        self.trailers =
+            read_headers(&mut self.inner, DEFAULT_MAX_HEADERS, DEFAULT_MAX_LINE_LENGTH)?;
+        Ok(())
+    }
+
+    /// Reads the size line of the next chunk, chunk extensions after ';' carry nothing we use
+    fn next_size(&mut self) -> io::Result<u64> {
+        let mut line = String::new();
+        read_line(&mut self.inner, &mut line, DEFAULT_MAX_LINE_LENGTH)?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        u64::from_str_radix(size, 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid chunk size"))
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            self.remaining = self.next_size()?;
+            if self.remaining == 0 {
+                self.done = true;
+                self.read_trailers()?;
+                return Ok(0);
+            }
+        }
+        let max = self.remaining.min(buf.len() as u64) as usize;
+        let read = self.inner.read(&mut buf[..max])?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= read as u64;
+        if self.remaining == 0 {
+            // the CRLF ending the chunk data, anything before it means the size was wrong
+            let mut line = String::new();
+            if read_line(&mut self.inner, &mut line, DEFAULT_MAX_LINE_LENGTH)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if line != "\r\n" && line != "\n" {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "chunk data longer than its size",
+                ));
+            }
+        }
+        Ok(read)
+    }
+}
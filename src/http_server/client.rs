@@ -0,0 +1,147 @@
+use std::{
+    io::{self, BufReader, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use http_base::http::http_structs::{HttpData, HttpHeaders, HttpMethod, HttpResponse};
+
+#[cfg(feature = "client")]
+use super::response::{MAX_UPSTREAM_BODY, UPSTREAM_READ_TIMEOUT};
+use super::{chunked::ChunkedWriter, method::HttpMethodExt, response::read_response};
+
+/// Minimal blocking HTTP/1.1 client, every request uses its own connection
+#[cfg(feature = "client")]
+pub struct HttpClient;
+
+#[cfg(feature = "client")]
+impl HttpClient {
+    /// Sends a request to an `http://` url and reads the complete response. Host, Content-Length
+    /// and Connection headers are set by the client, streamed bodies are sent chunked.
+    /// The response is buffered in memory, bodies over 10 MiB are an `io::ErrorKind::InvalidData`
+    /// error and reads blocking for more than 30 seconds an `io::ErrorKind::WouldBlock` or
+    /// `TimedOut` error. `https://` urls are rejected with `io::ErrorKind::InvalidInput`
+    /// Example:
+    /// ```rust,no_run
+    /// use http_serv::{HttpMethod, HttpStatus, http_server::client::HttpClient};
+    ///
+    /// let response = HttpClient::request(HttpMethod::GET, "http://127.0.0.1:8443/users", Vec::new(), None).unwrap();
+    /// assert_eq!(response.status, HttpStatus::Ok);
+    /// ```
+    pub fn request(
+        method: HttpMethod,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: Option<HttpData>,
+    ) -> io::Result<HttpResponse> {
+        let (authority, path) = split_url(url)?;
+        let mut extra_headers: Vec<(String, String)> = headers
+            .into_iter()
+            .filter(|(key, _)| {
+                !["Host", "Content-Length", "Transfer-Encoding", "Connection"]
+                    .iter()
+                    .any(|name| key.eq_ignore_ascii_case(name))
+            })
+            .collect();
+        extra_headers.push((String::from("Host"), authority.to_owned()));
+        match &body {
+            Some(HttpData::Bytes(bytes)) => {
+                extra_headers.push((String::from("Content-Length"), bytes.len().to_string()))
+            }
+            Some(HttpData::Stream(_)) => {
+                extra_headers.push((String::from("Transfer-Encoding"), String::from("chunked")))
+            }
+            None => {}
+        }
+        extra_headers.push((String::from("Connection"), String::from("close")));
+        let headers = HttpHeaders {
+            method,
+            path,
+            protocol: String::from("HTTP/1.1"),
+            extra_headers,
+        };
+        send(
+            &with_default_port(authority),
+            &headers,
+            body,
+            MAX_UPSTREAM_BODY,
+            UPSTREAM_READ_TIMEOUT,
+        )
+    }
+}
+
+/// Sends a request on a new connection to addr and reads the complete response. The headers are
+/// sent as they are, framing headers for the body included. Responses are read with max_size as
+/// the largest body buffered and read_timeout as the longest time a single read may block
+pub(crate) fn send(
+    addr: &str,
+    headers: &HttpHeaders,
+    body: Option<HttpData>,
+    max_size: u64,
+    read_timeout: Duration,
+) -> io::Result<HttpResponse> {
+    let mut stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(read_timeout))?;
+    stream.write_all(&serialize_head(headers))?;
+    match body {
+        Some(HttpData::Bytes(bytes)) => stream.write_all(&bytes)?,
+        Some(HttpData::Stream(mut read)) => {
+            let mut writer = ChunkedWriter::new(&mut stream);
+            io::copy(&mut read.0, &mut writer)?;
+            writer.finish()?;
+        }
+        None => {}
+    }
+    stream.flush()?;
+    let head_request = headers.method == HttpMethod::HEAD;
+    read_response(&mut BufReader::new(stream), head_request, max_size)
+}
+
+/// Splits an `http://` url into authority and path, the path includes the query
+#[cfg(feature = "client")]
+fn split_url(url: &str) -> io::Result<(&str, String)> {
+    let Some(rest) = url.strip_prefix("http://") else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "only http:// urls are supported",
+        ));
+    };
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(idx) if rest[idx..].starts_with('/') => (&rest[..idx], rest[idx..].to_owned()),
+        // http://host?query has the root as path
+        Some(idx) => (&rest[..idx], format!("/{}", &rest[idx..])),
+        None => (rest, String::from("/")),
+    };
+    if authority.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "url has no host",
+        ));
+    }
+    Ok((authority, path))
+}
+
+/// Adds port 80 to an authority without port, bracketed IPv6 hosts contain colons themselves
+#[cfg(feature = "client")]
+fn with_default_port(authority: &str) -> String {
+    let host_end = authority.rfind(']').unwrap_or(0);
+    match authority[host_end..].contains(':') {
+        true => authority.to_owned(),
+        false => format!("{authority}:80"),
+    }
+}
+
+/// Serializes the request line and headers including the blank line ending the head
+fn serialize_head(headers: &HttpHeaders) -> Vec<u8> {
+    let mut head = format!(
+        "{} {} {}\r\n",
+        headers.method.as_str(),
+        headers.path,
+        headers.protocol
+    );
+    for (key, value) in &headers.extra_headers {
+        head.push_str(&format!("{key}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    head.into_bytes()
+}
@@ -0,0 +1,62 @@
+use http_base::http::http_structs::{HttpMethod, HttpRequest, HttpResponse, HttpStatus};
+
+use super::{
+    date::DateTime, request::HttpRequestExt, response::HttpResponseExt, status::HttpStatusExt,
+};
+
+/// Computes a strong entity tag for a body using 64 bit FNV-1a, which is stable across builds
+pub(crate) fn etag_for(body: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in body {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("\"{hash:016x}\"")
+}
+
+/// Checks an If-None-Match header against an entity tag using weak comparison
+fn none_match(header: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Checks an If-Modified-Since header against a Last-Modified header, unparsable dates count as modified
+fn not_modified_since(header: &str, last_modified: &str) -> bool {
+    match (
+        DateTime::parse_http_date(header),
+        DateTime::parse_http_date(last_modified),
+    ) {
+        (Some(since), Some(last_modified)) => {
+            last_modified.to_system_time() <= since.to_system_time()
+        }
+        _ => false,
+    }
+}
+
+/// Turns a 200 response to a GET or HEAD request into 304 Not Modified if the client already has it
+pub(crate) fn apply_conditional(request: &HttpRequest, response: &mut HttpResponse) {
+    let method = &request.http_headers.method;
+    if *method != HttpMethod::GET && *method != HttpMethod::HEAD || response.status.code() != 200 {
+        return;
+    }
+    // If-Modified-Since is only evaluated when the client did not send If-None-Match
+    let not_modified = match request.header("If-None-Match") {
+        Some(header) => response
+            .header("ETag")
+            .is_some_and(|etag| none_match(header, etag)),
+        None => match (
+            request.header("If-Modified-Since"),
+            response.header("Last-Modified"),
+        ) {
+            (Some(header), Some(last_modified)) => not_modified_since(header, last_modified),
+            _ => false,
+        },
+    };
+    if not_modified {
+        response.status = HttpStatus::NotModified;
+        response.data = None;
+    }
+}
@@ -0,0 +1,124 @@
+use http_base::http::http_structs::{HttpMethod, HttpRequest, HttpResponse, HttpStatus};
+
+use super::{request::HttpRequestExt, response::HttpResponseExt};
+
+/// Cross origin resource sharing configuration. Preflight requests are answered automatically and
+/// responses to allowed origins get the matching Access-Control-Allow-* headers
+/// Example:
+/// ```rust
+/// use http_serv::http_server::cors::Cors;
+///
+/// let cors = Cors {
+///     allowed_origins: vec![String::from("https://example.com")],
+///     allowed_methods: vec![String::from("GET"), String::from("POST")],
+///     allowed_headers: vec![String::from("Content-Type")],
+///     allow_credentials: true,
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Cors {
+    /// Origins allowed to read responses, `*` allows any origin
+    pub allowed_origins: Vec<String>,
+    /// Methods allowed in preflight requests, `*` allows any method
+    pub allowed_methods: Vec<String>,
+    /// Request headers allowed in preflight requests, `*` allows any header
+    pub allowed_headers: Vec<String>,
+    /// Sends `Access-Control-Allow-Credentials: true`. As browsers reject `*` for credentialed
+    /// requests, the requested origin, method and headers are reflected instead of the wildcard
+    pub allow_credentials: bool,
+}
+
+impl Cors {
+    /// Allows every origin, method and header with credentials, reflecting whatever the request
+    /// asked for. Meant for local development only, it lets any website make credentialed
+    /// requests to the server and read the responses
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::cors::Cors;
+    ///
+    /// let cors = Cors::permissive();
+    /// assert!(cors.allow_credentials);
+    /// ```
+    pub fn permissive() -> Self {
+        Self {
+            allowed_origins: vec![String::from("*")],
+            allowed_methods: vec![String::from("*")],
+            allowed_headers: vec![String::from("*")],
+            allow_credentials: true,
+        }
+    }
+
+    /// Returns the Access-Control-Allow-Origin value for a request origin, None if it is not allowed
+    pub(crate) fn allowed_origin(&self, origin: &str) -> Option<String> {
+        if self.allowed_origins.iter().any(|allowed| allowed == origin) {
+            return Some(origin.to_owned());
+        }
+        if self.allowed_origins.iter().any(|allowed| allowed == "*") {
+            return Some(self.wildcard_or(origin));
+        }
+        None
+    }
+
+    /// Adds the CORS headers for a request origin to a response
+    pub(crate) fn apply(&self, origin: &str, response: &mut HttpResponse) {
+        let Some(allowed_origin) = self.allowed_origin(origin) else {
+            return;
+        };
+        // the response differs per origin unless the wildcard is sent
+        if allowed_origin != "*" {
+            response.set_header("Vary", "Origin");
+        }
+        response.set_header("Access-Control-Allow-Origin", &allowed_origin);
+        if self.allow_credentials {
+            response.set_header("Access-Control-Allow-Credentials", "true");
+        }
+    }
+
+    /// Checks whether a request is a CORS preflight request
+    pub(crate) fn is_preflight(request: &HttpRequest) -> bool {
+        request.http_headers.method == HttpMethod::OPTIONS
+            && request.header("Origin").is_some()
+            && request.header("Access-Control-Request-Method").is_some()
+    }
+
+    /// Builds the 204 answer to a preflight request
+    pub(crate) fn preflight(&self, request: &HttpRequest) -> HttpResponse {
+        let mut response = HttpResponse::new(String::from("1.1"), HttpStatus::NoContent, None, None);
+        let Some(origin) = request.header("Origin") else {
+            return response;
+        };
+        self.apply(origin, &mut response);
+        if response.header("Access-Control-Allow-Origin").is_none() {
+            return response;
+        }
+        let methods = self.allowed_list(
+            &self.allowed_methods,
+            request.header("Access-Control-Request-Method"),
+        );
+        response.set_header("Access-Control-Allow-Methods", &methods);
+        let headers = self.allowed_list(
+            &self.allowed_headers,
+            request.header("Access-Control-Request-Headers"),
+        );
+        if !headers.is_empty() {
+            response.set_header("Access-Control-Allow-Headers", &headers);
+        }
+        response
+    }
+
+    /// Joins an allow list, resolving the wildcard against what the request asked for
+    fn allowed_list(&self, allowed: &[String], requested: Option<&str>) -> String {
+        if allowed.iter().any(|allowed| allowed == "*") {
+            return self.wildcard_or(requested.unwrap_or_default());
+        }
+        allowed.join(", ")
+    }
+
+    /// Credentialed requests may not use the wildcard, so the requested value is reflected instead
+    fn wildcard_or(&self, requested: &str) -> String {
+        match self.allow_credentials {
+            true => requested.to_owned(),
+            false => String::from("*"),
+        }
+    }
+}
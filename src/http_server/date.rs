@@ -0,0 +1,133 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// A UTC calendar date and time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DateTime {
+    pub year: i64,
+    /// 1 to 12
+    pub month: u32,
+    /// 1 to 31
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime {
+    /// Converts a point in time to its UTC calendar date, times before the epoch are clamped to it
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default() as i64;
+        let (days, secs_of_day) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+        // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (secs_of_day / 3600) as u32,
+            minute: (secs_of_day % 3600 / 60) as u32,
+            second: (secs_of_day % 60) as u32,
+        }
+    }
+
+    /// Days since the unix epoch
+    fn days(self) -> i64 {
+        // days from civil, the inverse of from_system_time
+        let year = self.year - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5
+            + i64::from(self.day)
+            - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    /// Converts the date back to a point in time, dates before the epoch are clamped to it
+    pub fn to_system_time(self) -> SystemTime {
+        let secs = self.days() * 86400
+            + i64::from(self.hour) * 3600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second);
+        UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)
+    }
+
+    /// Formats the date as IMF-fixdate used by http headers, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+    pub fn to_http_date(self) -> String {
+        format!(
+            "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+            WEEKDAYS[(self.days() + 4).rem_euclid(7) as usize],
+            self.day,
+            MONTHS[self.month as usize - 1],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+
+    /// Parses an IMF-fixdate. Other formats, zones other than GMT and invalid dates yield None
+    pub fn parse_http_date(value: &str) -> Option<Self> {
+        let (weekday, rest) = value.trim().split_once(", ")?;
+        let mut parts = rest.split(' ');
+        let (day, month, year, time, zone) = (
+            parts.next()?,
+            parts.next()?,
+            parts.next()?,
+            parts.next()?,
+            parts.next()?,
+        );
+        if parts.next().is_some() || zone != "GMT" || day.len() != 2 || year.len() != 4 {
+            return None;
+        }
+        let mut time = time.split(':');
+        let date = Self {
+            year: year.parse().ok()?,
+            month: MONTHS.iter().position(|name| *name == month)? as u32 + 1,
+            day: day.parse().ok()?,
+            hour: time.next()?.parse().ok()?,
+            minute: time.next()?.parse().ok()?,
+            second: time.next()?.parse().ok()?,
+        };
+        // round tripping rejects out of range fields like Feb 30 or 25:00:00 as well as a wrong weekday
+        let normalized = Self::from_system_time(date.to_system_time());
+        (time.next().is_none()
+            && normalized == date
+            && normalized.to_http_date().starts_with(weekday))
+        .then_some(date)
+    }
+
+    /// Formats the date as used by the common log format, e.g. `10/Oct/2000:13:55:36 +0000`
+    #[cfg(feature = "log")]
+    pub fn to_common_log(self) -> String {
+        format!(
+            "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+            self.day,
+            MONTHS[self.month as usize - 1],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    collections::HashMap,
+    net::{Shutdown, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// Shutdown state shared by the accept loop, the workers and the deadline thread
+#[derive(Default)]
+pub(crate) struct Drain {
+    requested: AtomicBool,
+    expired: AtomicBool,
+    next_id: AtomicU64,
+    // Id Connection, clones of the streams being served
+    connections: Mutex<HashMap<u64, TcpStream>>,
+}
+
+impl Drain {
+    /// Marks the shutdown as requested, returns false if it already was
+    pub fn request(&self) -> bool {
+        !self.requested.swap(true, Ordering::SeqCst)
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Tracks the connection until the returned guard is dropped. Returns None once the deadline
+    /// passed, the connection is then dropped without being served
+    pub fn track(&self, stream: &TcpStream) -> Option<TrackedConnection<'_>> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        // without a clone the connection cannot be closed early, it is still served
+        if let Ok(clone) = stream.try_clone() {
+            self.lock().insert(id, clone);
+        }
+        let tracked = TrackedConnection(self, id);
+        // checked after inserting, so connections tracked while closing are not missed
+        match self.expired.load(Ordering::SeqCst) {
+            true => None,
+            false => Some(tracked),
+        }
+    }
+
+    /// Closes every tracked connection once the shutdown deadline passed. Handlers reading or
+    /// writing the connection fail, which ends them
+    pub fn expire(&self) {
+        self.expired.store(true, Ordering::SeqCst);
+        for stream in self.lock().values() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, TcpStream>> {
+        // a poisoned lock only means another connection panicked while holding it
+        self.connections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Removes the connection from the tracked ones when dropped
+pub(crate) struct TrackedConnection<'a>(&'a Drain, u64);
+
+impl Drop for TrackedConnection<'_> {
+    fn drop(&mut self) {
+        self.0.lock().remove(&self.1);
+    }
+}
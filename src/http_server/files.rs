@@ -0,0 +1,173 @@
+use std::{
+    fmt::Write,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use http_base::http::http_structs::{HttpData, HttpRequest, HttpResponse, HttpStatus};
+
+use super::{html, response::HttpResponseExt};
+
+/// Serves the file below dir named by rest, the part of the request path after the prefix.
+/// Paths ending in a slash name directories and serve their index file, or a listing if there is
+/// none and listing is enabled. Directories requested without the slash are redirected to it so
+/// relative links resolve. Anything that cannot be served is answered with 404
+pub(crate) fn serve(
+    dir: &Path,
+    index: &str,
+    listing: bool,
+    request: &HttpRequest,
+    rest: &str,
+) -> HttpResponse {
+    let Some(relative) = resolve(rest) else {
+        return not_found();
+    };
+    let path = dir.join(relative);
+    let (route, query) = match request.http_headers.path.split_once('?') {
+        Some((route, query)) => (route, Some(query)),
+        None => (request.http_headers.path.as_str(), None),
+    };
+    match (path.is_dir(), route.ends_with('/')) {
+        (true, true) if listing && !path.join(index).is_file() => list_dir(&path, route, rest),
+        (true, true) => file_response(&path.join(index)),
+        (true, false) => {
+            let location = match query {
+                Some(query) => format!("{route}/?{query}"),
+                None => format!("{route}/"),
+            };
+            HttpResponse::redirect(HttpStatus::MovedPermanently, &location)
+        }
+        // a file of the same name does not answer a directory request
+        (false, true) => not_found(),
+        (false, false) => file_response(&path),
+    }
+}
+
+/// Turns the rest of a request path into a relative path, None if a segment is not a plain file
+/// name and could leave the directory
+fn resolve(rest: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for segment in rest.split('/').filter(|segment| !segment.is_empty()) {
+        let segment = percent_decode(segment)?;
+        // rejects `.`, `..`, roots, drive prefixes and decoded separators
+        let mut components = Path::new(&segment).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            return None;
+        }
+        relative.push(segment);
+    }
+    Some(relative)
+}
+
+/// Decodes `%XX` escapes, None for malformed escapes or names that are not UTF-8
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] != b'%' {
+            decoded.push(bytes[idx]);
+            idx += 1;
+            continue;
+        }
+        let hex = segment.get(idx + 1..idx + 3)?;
+        if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+        decoded.push(u8::from_str_radix(hex, 16).ok()?);
+        idx += 3;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn file_response(path: &Path) -> HttpResponse {
+    if !path.is_file() {
+        return not_found();
+    }
+    match HttpResponse::from_file(path) {
+        Ok(response) => response,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => not_found(),
+        Err(_err) => {
+            #[cfg(feature = "log")]
+            log::error!("Failed to open {}: {_err}", path.display());
+            HttpResponse::new(
+                String::from("1.1"),
+                HttpStatus::InternalServerError,
+                None,
+                None,
+            )
+        }
+    }
+}
+
+/// Builds an HTML page linking the entries of a directory, subdirectories first and both sorted
+/// by name. Names that are not UTF-8 cannot be requested and are left out
+fn list_dir(path: &Path, route: &str, rest: &str) -> HttpResponse {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_err) => {
+            #[cfg(feature = "log")]
+            log::error!("Failed to list {}: {_err}", path.display());
+            return not_found();
+        }
+    };
+    // Directory Name Size
+    let mut entries: Vec<(bool, String, u64)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // follows symlinks like serving does
+            let metadata = fs::metadata(entry.path()).ok()?;
+            Some((metadata.is_dir(), name, metadata.len()))
+        })
+        .collect();
+    entries.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    let title = html::escape(route);
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n<body>\n<h1>Index of {title}</h1>\n<ul>\n"
+    );
+    if !rest.is_empty() {
+        page.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for (is_dir, name, size) in entries {
+        let (href, name) = (percent_encode(&name), html::escape(&name));
+        let _ = match is_dir {
+            true => writeln!(page, "<li><a href=\"{href}/\">{name}/</a></li>"),
+            false => writeln!(page, "<li><a href=\"{href}\">{name}</a> {size} bytes</li>"),
+        };
+    }
+    page.push_str("</ul>\n</body>\n</html>\n");
+
+    let mut response = HttpResponse::new(
+        String::from("1.1"),
+        HttpStatus::Ok,
+        None,
+        Some(HttpData::Bytes(page.into_bytes())),
+    );
+    response.set_header("Content-Type", "text/html; charset=utf-8");
+    response
+}
+
+/// Encodes everything but unreserved characters, so a file name is a single relative path segment
+fn percent_encode(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            byte => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::new(String::from("1.1"), HttpStatus::NotFound, None, None)
+}
@@ -0,0 +1,447 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read},
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use http_base::http::http_structs::{HttpHeaders, HttpMethod, HttpStatus};
+
+use super::method::HttpMethodExt;
+
+/// Header lines allowed in a head unless configured otherwise, upstream responses are held to it
+pub(crate) const DEFAULT_MAX_HEADERS: usize = 100;
+/// Longest line allowed in a head unless configured otherwise, upstream responses are held to it
+pub(crate) const DEFAULT_MAX_LINE_LENGTH: usize = 8 * 1024;
+
+/// Bounds on the request head, checked before it is parsed
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HeadLimits {
+    pub max_headers: usize,
+    pub max_line_length: usize,
+    /// How long the complete request head may take to arrive
+    pub timeout: Duration,
+}
+
+/// Reason a request head could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The request line is empty
+    MissingMethod,
+    /// The method is not an HttpMethod
+    UnknownMethod(String),
+    /// The request line ends after the method
+    MissingPath,
+    /// The request line ends after the path
+    MissingProtocol,
+    /// The request line has more than three parts or no HTTP protocol, or a header line has no
+    /// colon, an empty name or whitespace in its name
+    MalformedLine(String),
+    /// The request line names a protocol version other than HTTP/1.0 and HTTP/1.1
+    UnsupportedVersion(String),
+}
+
+impl ParseError {
+    /// Returns the status the request is rejected with
+    pub(crate) fn status(&self) -> HttpStatus {
+        match self {
+            ParseError::UnsupportedVersion(_) => HttpStatus::HttpVersionNotSupported,
+            // well formed methods which are not an HttpMethod are answered with 501 before they
+            // become a ParseError, the ones left are not method tokens at all
+            ParseError::MissingMethod
+            | ParseError::UnknownMethod(_)
+            | ParseError::MissingPath
+            | ParseError::MissingProtocol
+            | ParseError::MalformedLine(_) => HttpStatus::BadRequest,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingMethod => write!(f, "missing method"),
+            ParseError::UnknownMethod(method) => write!(f, "unknown method {method:?}"),
+            ParseError::MissingPath => write!(f, "missing path"),
+            ParseError::MissingProtocol => write!(f, "missing protocol"),
+            ParseError::MalformedLine(line) => write!(f, "malformed line {line:?}"),
+            ParseError::UnsupportedVersion(protocol) => {
+                write!(f, "unsupported protocol version {protocol}")
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Result of reading a request head
+pub(crate) enum RequestHead {
+    /// The complete head was received and parsed
+    Head(HttpHeaders),
+    /// The head has more header lines or longer lines than allowed
+    TooLarge,
+    /// The request line is well formed but its method is not an HttpMethod, e.g. TRACE or CONNECT
+    UnsupportedMethod {
+        method: String,
+        path: String,
+        headers: Vec<(String, String)>,
+    },
+    /// The head was received but is not a valid request head
+    Malformed(ParseError),
+    /// The complete head did not arrive within the head timeout
+    TimedOut,
+    /// The connection closed before the head was complete
+    Closed,
+}
+
+/// Reads the request line and headers, leaving the body on the stream so the request can be
+/// inspected before its body is sent. Limits are checked while the head arrives, so oversized
+/// heads are rejected without buffering them completely. The whole head has to arrive within
+/// the head timeout, clients sending it slowly cannot hold the connection open any longer.
+/// The head is returned with its bytes as received including the blank line, which are empty
+/// if the head did not arrive completely
+pub(crate) fn read_head(
+    stream: &TcpStream,
+    limits: HeadLimits,
+) -> io::Result<(RequestHead, Vec<u8>)> {
+    let previous_timeout = stream.read_timeout()?;
+    let raw = receive_head(stream, limits);
+    stream.set_read_timeout(previous_timeout)?;
+    let raw = match raw? {
+        Ok(raw) => raw,
+        Err(rejected) => return Ok((rejected, Vec::new())),
+    };
+    // the blank line is not part of the last header
+    let head = String::from_utf8_lossy(&raw);
+    let head = head.trim_end_matches(['\r', '\n']);
+    let head = match parse_head(head) {
+        Ok(ParsedHead::Head(head)) => RequestHead::Head(head),
+        Ok(ParsedHead::UnsupportedMethod {
+            method,
+            path,
+            headers,
+        }) => RequestHead::UnsupportedMethod {
+            method,
+            path,
+            headers,
+        },
+        Err(err) => RequestHead::Malformed(err),
+    };
+    Ok((head, raw))
+}
+
+/// Receives the head up to and including the blank line, lines may end in `\r\n` or `\n`. Bytes
+/// are peeked before they are read, so nothing after the head is taken from the stream
+fn receive_head(
+    stream: &TcpStream,
+    limits: HeadLimits,
+) -> io::Result<Result<Vec<u8>, RequestHead>> {
+    // every line including the request line at full length, plus the blank line
+    let max_size = (limits.max_headers + 1) * (limits.max_line_length + 2) + 2;
+    let deadline = Instant::now() + limits.timeout;
+    let mut check = LimitCheck::default();
+    let mut head = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(Err(RequestHead::TimedOut));
+        }
+        // blocks until more data arrives, buffered data is returned immediately
+        stream.set_read_timeout(Some(remaining))?;
+        let length = match stream.peek(&mut buf) {
+            Ok(0) => return Ok(Err(RequestHead::Closed)),
+            Ok(length) => length,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(Err(RequestHead::TimedOut))
+            }
+            Err(err) => return Err(err),
+        };
+        // the line end before the blank line may have been received earlier
+        let received = head.len();
+        head.extend_from_slice(&buf[..length]);
+        let end = head_end(&head, received.saturating_sub(2));
+        let consumed = match end {
+            Some(end) => end - received,
+            None => length,
+        };
+        let mut stream_ref = stream;
+        stream_ref.read_exact(&mut buf[..consumed])?;
+        // bytes peeked after the blank line belong to the body and are not checked
+        let checked = end.unwrap_or(head.len());
+        if check.exceeds(&head[..checked], limits) || (end.is_none() && head.len() >= max_size) {
+            return Ok(Err(RequestHead::TooLarge));
+        }
+        if let Some(end) = end {
+            head.truncate(end);
+            return Ok(Ok(head));
+        }
+    }
+}
+
+/// Returns the length of the head up to and including the blank line ending it, looking for a line
+/// end from position from on. Either line may end in `\r\n` or `\n`
+fn head_end(head: &[u8], from: usize) -> Option<usize> {
+    (from..head.len()).find_map(|position| match &head[position..] {
+        [b'\n', b'\n', ..] => Some(position + 2),
+        [b'\n', b'\r', b'\n', ..] => Some(position + 3),
+        _ => None,
+    })
+}
+
+/// Checks a head against the limits while it arrives. Only bytes not seen by an earlier check
+/// are scanned, so receiving the head in many small reads stays linear in its size. The request
+/// line counts towards the line length but not towards the number of headers
+#[derive(Default)]
+struct LimitCheck {
+    /// Bytes of the head already scanned for line ends
+    scanned: usize,
+    /// Start of the line that has not ended yet
+    line_start: usize,
+    /// Whether the request line has ended
+    request_line: bool,
+    headers: usize,
+}
+
+impl LimitCheck {
+    /// Checks the bytes received since the last call, head always holds everything received
+    fn exceeds(&mut self, head: &[u8], limits: HeadLimits) -> bool {
+        for position in self.scanned..head.len() {
+            if head[position] != b'\n' {
+                continue;
+            }
+            let line = &head[self.line_start..position];
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            self.line_start = position + 1;
+            if line.len() > limits.max_line_length {
+                return true;
+            }
+            if !self.request_line {
+                self.request_line = true;
+            } else if !line.is_empty() {
+                self.headers += 1;
+                if self.headers > limits.max_headers {
+                    return true;
+                }
+            }
+        }
+        self.scanned = head.len();
+        // the line still arriving may already end in the carriage return
+        head.len() - self.line_start > limits.max_line_length + 1
+    }
+}
+
+/// Parses a request head, the request line followed by header lines without the blank line
+/// ending them. Lines may end in `\r\n` or `\n`
+/// Example:
+/// ```rust
+/// use http_serv::{HttpMethod, ParseError, http_server::head::parse_request_head};
+///
+/// let head = parse_request_head("GET /users HTTP/1.1\r\nHost: localhost").unwrap();
+/// assert_eq!(head.method, HttpMethod::GET);
+/// assert_eq!(head.path, "/users");
+///
+/// assert_eq!(parse_request_head("GET /users").err(), Some(ParseError::MissingProtocol));
+/// assert_eq!(
+///     parse_request_head("BREW /pot HTTP/1.1").err(),
+///     Some(ParseError::UnknownMethod(String::from("BREW")))
+/// );
+/// ```
+pub fn parse_request_head(head: &str) -> Result<HttpHeaders, ParseError> {
+    match parse_head(head)? {
+        ParsedHead::Head(headers) => Ok(headers),
+        ParsedHead::UnsupportedMethod { method, .. } => Err(ParseError::UnknownMethod(method)),
+    }
+}
+
+/// A request head that parsed, see `parse_head`
+enum ParsedHead {
+    Head(HttpHeaders),
+    /// The request line is well formed but its method is not an HttpMethod
+    UnsupportedMethod {
+        method: String,
+        path: String,
+        headers: Vec<(String, String)>,
+    },
+}
+
+/// Parses a request line followed by header lines. A well formed request line whose method
+/// token is not an HttpMethod, e.g. TRACE or CONNECT, is returned as UnsupportedMethod
+fn parse_head(head: &str) -> Result<ParsedHead, ParseError> {
+    let mut lines = head
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line));
+    let request_line = lines.next().unwrap_or_default();
+    let [method, path, protocol] = split_request_line(request_line)?;
+    let Some(version) = protocol.strip_prefix("HTTP/") else {
+        return Err(ParseError::MalformedLine(request_line.to_owned()));
+    };
+    if version != "1.0" && version != "1.1" {
+        return Err(ParseError::UnsupportedVersion(protocol.to_owned()));
+    }
+    let headers = lines.map(parse_header).collect::<Result<Vec<_>, _>>()?;
+    match HttpMethod::from_name(method) {
+        Ok(method) => Ok(ParsedHead::Head(HttpHeaders {
+            method,
+            path: path.to_owned(),
+            protocol: protocol.to_owned(),
+            extra_headers: headers,
+        })),
+        Err(_) if method.bytes().all(|byte| byte.is_ascii_alphabetic()) => {
+            Ok(ParsedHead::UnsupportedMethod {
+                method: method.to_owned(),
+                path: path.to_owned(),
+                headers,
+            })
+        }
+        Err(_) => Err(ParseError::UnknownMethod(method.to_owned())),
+    }
+}
+
+/// Parses a header line of a request head, a chunked body's trailers or an upstream response.
+/// Whitespace is not allowed in the name, which also rejects the obsolete line folding of values,
+/// as RFC 9112 requires
+pub(crate) fn parse_header(line: &str) -> Result<(String, String), ParseError> {
+    match line.split_once(':') {
+        Some((key, value))
+            if !key.is_empty() && !key.bytes().any(|byte| byte.is_ascii_whitespace()) =>
+        {
+            Ok((key.to_owned(), value.trim().to_owned()))
+        }
+        _ => Err(ParseError::MalformedLine(line.to_owned())),
+    }
+}
+
+/// Splits a request line into method, path and protocol. Runs of whitespace separate the tokens,
+/// lines with more than three tokens are not request lines
+fn split_request_line(line: &str) -> Result<[&str; 3], ParseError> {
+    let mut tokens = line.split_whitespace();
+    let method = tokens.next().ok_or(ParseError::MissingMethod)?;
+    let path = tokens.next().ok_or(ParseError::MissingPath)?;
+    let protocol = tokens.next().ok_or(ParseError::MissingProtocol)?;
+    match tokens.next() {
+        Some(_) => Err(ParseError::MalformedLine(line.to_owned())),
+        None => Ok([method, path, protocol]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_server::status::HttpStatusExt;
+
+    #[test]
+    fn request_line_with_repeated_spaces_is_parsed() {
+        let Ok(ParsedHead::Head(head)) = parse_head("GET  /  HTTP/1.1\r\nHost: localhost") else {
+            panic!("request line was rejected");
+        };
+        assert_eq!(head.method, HttpMethod::GET);
+        assert_eq!(head.path, "/");
+        assert_eq!(head.protocol, "HTTP/1.1");
+        assert_eq!(
+            head.extra_headers,
+            vec![(String::from("Host"), String::from("localhost"))]
+        );
+    }
+
+    #[test]
+    fn request_line_with_four_tokens_is_rejected() {
+        for head in [
+            "GET / HTTP/1.1 extra\r\nHost: localhost",
+            "TRACE / HTTP/1.1 extra\r\nHost: localhost",
+        ] {
+            let Err(err) = parse_head(head) else {
+                panic!("{head:?} was accepted");
+            };
+            assert_eq!(err, ParseError::MalformedLine(head.lines().next().unwrap().to_owned()));
+            assert_eq!(err.status(), HttpStatus::BadRequest);
+        }
+    }
+
+    #[test]
+    fn unknown_method_is_returned_with_headers() {
+        let Ok(ParsedHead::UnsupportedMethod {
+            method,
+            path,
+            headers,
+        }) = parse_head("TRACE /admin HTTP/1.1\r\nHost: localhost")
+        else {
+            panic!("TRACE was not returned as unsupported method");
+        };
+        assert_eq!((method.as_str(), path.as_str()), ("TRACE", "/admin"));
+        assert_eq!(headers, vec![(String::from("Host"), String::from("localhost"))]);
+    }
+
+    #[test]
+    fn unsupported_version_is_rejected_with_505() {
+        let Err(err) = parse_head("GET / HTTP/2.0\r\nHost: localhost") else {
+            panic!("HTTP/2.0 was accepted");
+        };
+        assert_eq!(err, ParseError::UnsupportedVersion(String::from("HTTP/2.0")));
+        assert_eq!(err.status().code(), 505);
+    }
+
+    #[test]
+    fn incomplete_request_line_names_missing_part() {
+        assert_eq!(parse_head("").err(), Some(ParseError::MissingMethod));
+        assert_eq!(parse_head("GET").err(), Some(ParseError::MissingPath));
+        assert_eq!(parse_head("GET /").err(), Some(ParseError::MissingProtocol));
+        assert_eq!(
+            parse_head("G3T / HTTP/1.1").err(),
+            Some(ParseError::UnknownMethod(String::from("G3T")))
+        );
+    }
+
+    #[test]
+    fn limits_are_checked_across_partial_reads() {
+        let limits = HeadLimits {
+            max_headers: 2,
+            max_line_length: 16,
+            timeout: Duration::from_secs(10),
+        };
+        let head = b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\n\r\n";
+        let mut check = LimitCheck::default();
+        for received in 1..=head.len() {
+            assert!(!check.exceeds(&head[..received], limits));
+        }
+        let head = b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n";
+        let mut check = LimitCheck::default();
+        assert!((1..=head.len()).any(|received| check.exceeds(&head[..received], limits)));
+        let mut check = LimitCheck::default();
+        assert!(check.exceeds(b"GET /a-path-too-long-for-the-limit", limits));
+    }
+
+    #[test]
+    fn head_ends_at_blank_line_with_either_line_end() {
+        for head in [
+            "GET / HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET / HTTP/1.1\nHost: a\n\n",
+            "GET / HTTP/1.1\nHost: a\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\n\n",
+        ] {
+            let with_body = format!("{head}body");
+            assert_eq!(head_end(with_body.as_bytes(), 0), Some(head.len()), "{head:?}");
+        }
+        assert_eq!(head_end(b"GET / HTTP/1.1\r\nHost: a\r\n", 0), None);
+        // the line end before the blank line was received in an earlier read
+        assert_eq!(head_end(b"GET / HTTP/1.1\n\r\n", 14), Some(17));
+    }
+
+    #[test]
+    fn header_without_colon_or_with_folding_is_rejected() {
+        for line in ["Host localhost", " folded", "Host : localhost", ": empty"] {
+            let head = format!("GET / HTTP/1.1\r\n{line}");
+            assert_eq!(
+                parse_head(&head).err(),
+                Some(ParseError::MalformedLine(line.to_owned()))
+            );
+        }
+    }
+}
@@ -0,0 +1,31 @@
+/// Escapes text for use in HTML content and quoted attribute values, so user input interpolated
+/// into hand-built pages cannot inject markup. `&`, `<`, `>`, `"` and `'` are replaced by
+/// character references, everything else is kept
+/// Example:
+/// ```rust
+/// use http_serv::http_server::html;
+///
+/// assert_eq!(html::escape("a & b"), "a &amp; b");
+/// assert_eq!(html::escape("<script>"), "&lt;script&gt;");
+/// assert_eq!(html::escape(r#"title="x""#), "title=&quot;x&quot;");
+/// assert_eq!(html::escape("it's"), "it&#39;s");
+/// assert_eq!(html::escape("plain text"), "plain text");
+///
+/// let name = "<img src=x onerror=alert(1)>";
+/// let page = format!("<p>Hello {}</p>", html::escape(name));
+/// assert_eq!(page, "<p>Hello &lt;img src=x onerror=alert(1)&gt;</p>");
+/// ```
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
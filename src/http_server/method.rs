@@ -0,0 +1,65 @@
+use std::{error::Error, fmt};
+
+use http_base::http::http_structs::HttpMethod;
+
+/// String conversions for HttpMethod
+pub trait HttpMethodExt: Sized {
+    /// Parses a method name as sent in the request line. Names are case sensitive as required by
+    /// RFC 9110, so `get` is not a known method
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpMethodExt};
+    ///
+    /// assert_eq!(HttpMethod::from_name("PATCH").unwrap(), HttpMethod::PATCH);
+    /// assert!(HttpMethod::from_name("BREW").is_err());
+    /// ```
+    fn from_name(name: &str) -> Result<Self, UnknownMethod>;
+
+    /// Returns the method name as sent in the request line
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpMethodExt};
+    ///
+    /// assert_eq!(HttpMethod::DELETE.as_str(), "DELETE");
+    /// ```
+    fn as_str(&self) -> &'static str;
+}
+
+impl HttpMethodExt for HttpMethod {
+    fn from_name(name: &str) -> Result<Self, UnknownMethod> {
+        match name {
+            "GET" => Ok(HttpMethod::GET),
+            "HEAD" => Ok(HttpMethod::HEAD),
+            "POST" => Ok(HttpMethod::POST),
+            "PUT" => Ok(HttpMethod::PUT),
+            "DELETE" => Ok(HttpMethod::DELETE),
+            "OPTIONS" => Ok(HttpMethod::OPTIONS),
+            "PATCH" => Ok(HttpMethod::PATCH),
+            _ => Err(UnknownMethod(name.to_owned())),
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::GET => "GET",
+            HttpMethod::HEAD => "HEAD",
+            HttpMethod::POST => "POST",
+            HttpMethod::PUT => "PUT",
+            HttpMethod::DELETE => "DELETE",
+            HttpMethod::OPTIONS => "OPTIONS",
+            HttpMethod::PATCH => "PATCH",
+        }
+    }
+}
+
+/// Error returned when parsing a method name that is not a known HttpMethod
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownMethod(pub String);
+
+impl fmt::Display for UnknownMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown http method {}", self.0)
+    }
+}
+
+impl Error for UnknownMethod {}
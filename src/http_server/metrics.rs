@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// Point in time snapshot of the server counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Metrics {
+    /// Requests parsed since the server was created
+    pub total_requests: u64,
+    /// Responses with a 1xx status, e.g. accepted websocket upgrades
+    pub responses_1xx: u64,
+    /// Responses with a 2xx status
+    pub responses_2xx: u64,
+    /// Responses with a 3xx status
+    pub responses_3xx: u64,
+    /// Responses with a 4xx status
+    pub responses_4xx: u64,
+    /// Responses with a 5xx status
+    pub responses_5xx: u64,
+    /// Connections currently being handled
+    pub active_connections: u64,
+    /// Bytes written for response heads and bodies
+    pub bytes_written: u64,
+}
+
+/// Counters updated by every worker while the server is running
+#[derive(Default)]
+pub(crate) struct Counters {
+    total_requests: AtomicU64,
+    // 1xx 2xx 3xx 4xx 5xx
+    responses: [AtomicU64; 5],
+    active_connections: AtomicU64,
+    bytes_written: AtomicU64,
+    // Route Status Count
+    routes: Mutex<HashMap<(String, u16), u64>>,
+}
+
+impl Counters {
+    pub fn request(&self) {
+        self.total_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a written response, statuses outside of 100..=599 are not attributed to a class
+    pub fn response(&self, status: u16, bytes: u64) {
+        if let Some(counter) = self
+            .responses
+            .get(usize::from(status / 100).wrapping_sub(1))
+        {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Counts a response written by the handler of a route template, e.g. `/users/:id`
+    pub fn route(&self, route: &str, status: u16) {
+        // a poisoned lock only means another request panicked while holding it, the counts are still usable
+        let mut routes = self
+            .routes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *routes.entry((route.to_owned(), status)).or_insert(0) += 1;
+    }
+
+    /// Counts the connection as active until the returned guard is dropped
+    pub fn connection(&self) -> ConnectionGuard<'_> {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(self)
+    }
+
+    pub fn snapshot(&self) -> Metrics {
+        let [responses_1xx, responses_2xx, responses_3xx, responses_4xx, responses_5xx] = self
+            .responses
+            .each_ref()
+            .map(|counter| counter.load(Ordering::Relaxed));
+        Metrics {
+            total_requests: self.total_requests.load(Ordering::Relaxed),
+            responses_1xx,
+            responses_2xx,
+            responses_3xx,
+            responses_4xx,
+            responses_5xx,
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Renders the counters in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let metrics = self.snapshot();
+        let mut out = String::new();
+        write_metric(
+            &mut out,
+            "http_requests_total",
+            "counter",
+            "Requests received by the server",
+            [(String::new(), metrics.total_requests)],
+        );
+        let classes = [
+            metrics.responses_1xx,
+            metrics.responses_2xx,
+            metrics.responses_3xx,
+            metrics.responses_4xx,
+            metrics.responses_5xx,
+        ];
+        write_metric(
+            &mut out,
+            "http_responses_total",
+            "counter",
+            "Responses written by status class",
+            (1..)
+                .zip(classes)
+                .map(|(class, count)| (format!("class=\"{class}xx\""), count)),
+        );
+        write_metric(
+            &mut out,
+            "http_active_connections",
+            "gauge",
+            "Connections currently being handled",
+            [(String::new(), metrics.active_connections)],
+        );
+        write_metric(
+            &mut out,
+            "http_response_bytes_total",
+            "counter",
+            "Bytes written for response heads and bodies",
+            [(String::new(), metrics.bytes_written)],
+        );
+        let mut routes: Vec<_> = self
+            .routes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .map(|((route, status), count)| (route.clone(), *status, *count))
+            .collect();
+        routes.sort();
+        write_metric(
+            &mut out,
+            "http_route_requests_total",
+            "counter",
+            "Requests answered by a route handler, by route template and status code",
+            routes.into_iter().map(|(route, status, count)| {
+                (
+                    format!("route=\"{}\",status=\"{status}\"", escape_label(&route)),
+                    count,
+                )
+            }),
+        );
+        out
+    }
+}
+
+/// Writes the HELP and TYPE lines of a metric followed by one sample per label set
+fn write_metric(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, u64)>,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{name} {value}");
+        } else {
+            let _ = writeln!(out, "{name}{{{labels}}} {value}");
+        }
+    }
+}
+
+/// Escapes backslashes, quotes and newlines in a label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Decrements the active connections once the connection is done, however it ended
+pub(crate) struct ConnectionGuard<'a>(&'a Counters);
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        self.0.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -1,2 +1,51 @@
+/// Access log formatting
+#[cfg(feature = "log")]
+pub mod access_log;
+/// Chunked transfer encoding
+mod chunked;
+/// Outbound http requests
+#[cfg(feature = "client")]
+pub mod client;
+// the proxy sends its requests through the client's connection code
+#[cfg(not(feature = "client"))]
+mod client;
+/// Conditional requests
+mod conditional;
+/// Cross origin resource sharing
+pub mod cors;
+/// Calendar date conversions
+mod date;
+/// Connection draining on shutdown
+mod drain;
+/// Static file serving
+mod files;
+/// HTML escaping
+pub mod html;
+/// Request head inspection before the body is read
+pub mod head;
+/// Helpers for http methods
+pub mod method;
+/// Server counters
+pub mod metrics;
+/// Content types of files
+mod mime;
+/// Header and body parsing shared by requests and responses
+mod parse;
+/// Forwarding requests to upstream servers
+pub mod proxy;
+/// Byte range requests
+mod range;
+/// Per client rate limiting
+mod rate_limit;
+/// Helpers for reading http requests
+pub mod request;
+/// Helpers for building http responses
+pub mod response;
 /// The basic http server module
 pub mod server;
+/// Server-sent events
+pub mod sse;
+/// Helpers for http status codes
+pub mod status;
+/// Websocket upgrade handshake
+pub mod websocket;
@@ -0,0 +1,469 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead, BufReader, Read},
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use super::{chunked::ChunkedReader, head::parse_header};
+
+/// Fields that control framing, routing, authentication or the request itself. RFC 9110 does not
+/// allow them in trailers, they are never merged into the headers
+const FORBIDDEN_TRAILERS: [&str; 12] = [
+    "Authorization",
+    "Connection",
+    "Content-Encoding",
+    "Content-Length",
+    "Content-Type",
+    "Cookie",
+    "Expect",
+    "Host",
+    "Proxy-Authorization",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+];
+
+/// Bounds on buffered request bodies, checked while the body is read
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BodyLimits {
+    pub max_size: u64,
+    /// How long the complete body may take to arrive
+    pub timeout: Duration,
+}
+
+/// A completely read body
+pub(crate) struct Body {
+    pub data: Vec<u8>,
+    /// Trailer fields following a chunked body, empty for other framings
+    pub trailers: Vec<(String, String)>,
+}
+
+/// Result of reading a buffered request body
+pub(crate) enum RequestBody {
+    /// The complete body was received, None if the headers frame no body
+    Body(Option<Body>),
+    /// The body is larger than allowed
+    TooLarge,
+    /// The Content-Length is invalid or the chunked encoding is malformed
+    Malformed,
+    /// The complete body did not arrive within the body timeout
+    TimedOut,
+}
+
+/// Error returned by read_body for bodies larger than the maximum size
+#[derive(Debug)]
+struct BodyTooLarge;
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "body too large")
+    }
+}
+
+impl Error for BodyTooLarge {}
+
+/// Reads header lines up to the blank line ending the head or the end of the reader, repeated
+/// headers are all kept. Lines are parsed as in the request head, malformed lines, more than
+/// max_headers headers or lines longer than max_line_length are an `io::ErrorKind::InvalidData`
+/// error
+pub(crate) fn read_headers(
+    reader: &mut impl BufRead,
+    max_headers: usize,
+    max_line_length: usize,
+) -> io::Result<Vec<(String, String)>> {
+    let mut headers = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if read_line(reader, &mut line, max_line_length)? == 0 {
+            return Ok(headers);
+        }
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        if headers.len() == max_headers {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "too many headers"));
+        }
+        let header =
+            parse_header(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        headers.push(header);
+    }
+}
+
+/// Reads a line of at most max_length bytes, so a line without an end cannot grow without bound.
+/// Longer lines are an `io::ErrorKind::InvalidData` error
+pub(crate) fn read_line(
+    reader: &mut impl BufRead,
+    line: &mut String,
+    max_length: usize,
+) -> io::Result<usize> {
+    // room for the line ending
+    let read = reader.by_ref().take(max_length as u64 + 2).read_line(line)?;
+    if read > max_length && !line.ends_with('\n') {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"));
+    }
+    Ok(read)
+}
+
+/// Reads the body of a request whose head has been read from the stream. A declared
+/// Content-Length is checked before anything is read, chunked bodies while they are decoded. The
+/// whole body has to arrive within the body timeout, as the head has to within the head timeout
+pub(crate) fn read_request_body(
+    stream: &TcpStream,
+    headers: &[(String, String)],
+    limits: BodyLimits,
+) -> io::Result<RequestBody> {
+    let previous_timeout = stream.read_timeout()?;
+    let mut reader = BufReader::new(DeadlineReader {
+        stream,
+        deadline: Instant::now() + limits.timeout,
+    });
+    let body = read_body(&mut reader, headers, limits.max_size);
+    stream.set_read_timeout(previous_timeout)?;
+    match body {
+        Ok(body) => Ok(RequestBody::Body(body)),
+        Err(err) if err.get_ref().is_some_and(|inner| inner.is::<BodyTooLarge>()) => {
+            Ok(RequestBody::TooLarge)
+        }
+        Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+            Ok(RequestBody::TimedOut)
+        }
+        Err(err) if err.kind() == io::ErrorKind::InvalidData => Ok(RequestBody::Malformed),
+        Err(err) => Err(err),
+    }
+}
+
+/// Reads a body framed by Transfer-Encoding or Content-Length. Returns None if the headers
+/// frame no body, responses then run until the connection closes. Bodies larger than max_size
+/// and invalid Content-Length values are an `io::ErrorKind::InvalidData` error
+pub(crate) fn read_body(
+    reader: &mut impl BufRead,
+    headers: &[(String, String)],
+    max_size: u64,
+) -> io::Result<Option<Body>> {
+    if is_chunked(headers)? {
+        let mut chunked = ChunkedReader::new(reader);
+        let mut data = Vec::new();
+        // one byte more than allowed tells an oversized body from one of exactly max_size
+        (&mut chunked)
+            .take(max_size.saturating_add(1))
+            .read_to_end(&mut data)?;
+        if data.len() as u64 > max_size {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, BodyTooLarge));
+        }
+        return Ok(Some(Body {
+            data,
+            trailers: chunked.into_trailers(),
+        }));
+    }
+    let Some(length) = content_length(headers)? else {
+        return Ok(None);
+    };
+    if length > max_size {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, BodyTooLarge));
+    }
+    let mut data = Vec::new();
+    if reader.by_ref().take(length).read_to_end(&mut data)? as u64 != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some(Body {
+        data,
+        trailers: Vec::new(),
+    }))
+}
+
+/// Returns the value of the Content-Length headers, None if there is none. Values which are not
+/// plain decimal numbers, e.g. `-1` or `5, 5`, and repeated headers with different values are
+/// an `io::ErrorKind::InvalidData` error, as RFC 9112 requires rejecting them
+pub(crate) fn content_length(headers: &[(String, String)]) -> io::Result<Option<u64>> {
+    let mut length = None;
+    for (_, value) in headers
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case("Content-Length"))
+    {
+        let value = value.trim();
+        let parsed = match value.bytes().all(|byte| byte.is_ascii_digit()) {
+            true => value.parse::<u64>().ok(),
+            false => None,
+        };
+        match (parsed, length) {
+            (Some(parsed), None) => length = Some(parsed),
+            (Some(parsed), Some(length)) if parsed == length => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid Content-Length {value:?}"),
+                ))
+            }
+        }
+    }
+    Ok(length)
+}
+
+/// Checks whether the body is framed by the chunked transfer coding. Transfer-Encoding lists the
+/// codings in the order they were applied, repeated headers form one list. chunked has to be the
+/// final coding, missing there or applied twice is an `io::ErrorKind::InvalidData` error. It is
+/// the only coding supported, others are an `io::ErrorKind::Unsupported` error
+pub(crate) fn is_chunked(headers: &[(String, String)]) -> io::Result<bool> {
+    let mut values = headers
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case("Transfer-Encoding"))
+        .peekable();
+    if values.peek().is_none() {
+        return Ok(false);
+    }
+    let codings: Vec<&str> = values
+        .flat_map(|(_, value)| value.split(','))
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty())
+        .collect();
+    let is_chunked = |coding: &&str| coding.eq_ignore_ascii_case("chunked");
+    if !codings.last().is_some_and(is_chunked) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "chunked is not the final transfer coding",
+        ));
+    }
+    if let Some(coding) = codings.iter().find(|coding| !is_chunked(coding)) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("unsupported transfer coding {coding:?}"),
+        ));
+    }
+    if codings.len() > 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "chunked transfer coding applied more than once",
+        ));
+    }
+    Ok(true)
+}
+
+/// Returns the trailer fields the client announced in the Trailer header. Undeclared trailers and
+/// fields not allowed in trailers are dropped, as the handler could not tell them from headers
+pub(crate) fn declared_trailers(
+    headers: &[(String, String)],
+    trailers: Vec<(String, String)>,
+) -> Vec<(String, String)> {
+    let declared: Vec<&str> = headers
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case("Trailer"))
+        .flat_map(|(_, value)| value.split(','))
+        .map(str::trim)
+        .collect();
+    trailers
+        .into_iter()
+        .filter(|(key, _)| {
+            let is_declared = declared.iter().any(|name| name.eq_ignore_ascii_case(key));
+            let is_forbidden = FORBIDDEN_TRAILERS
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(key));
+            #[cfg(feature = "log")]
+            if !is_declared {
+                log::debug!("Dropping undeclared trailer {key}");
+            }
+            is_declared && !is_forbidden
+        })
+        .collect()
+}
+
+/// Returns a reader over a request body framed by Transfer-Encoding or Content-Length, so the
+/// body can be consumed without buffering it. Requests framing neither have an empty body, an
+/// invalid Content-Length is an `io::ErrorKind::InvalidData` error
+pub(crate) fn body_reader<'a>(
+    reader: impl BufRead + 'a,
+    headers: &[(String, String)],
+) -> io::Result<Box<dyn Read + 'a>> {
+    if is_chunked(headers)? {
+        return Ok(Box::new(ChunkedReader::new(reader)));
+    }
+    Ok(Box::new(LengthReader {
+        inner: reader,
+        remaining: content_length(headers)?.unwrap_or(0),
+    }))
+}
+
+/// Reads exactly the given number of bytes, unlike `Read::take` ending early is an error so
+/// truncated bodies are not mistaken for complete ones
+struct LengthReader<R: Read> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for LengthReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let max = self.remaining.min(buf.len() as u64) as usize;
+        let read = self.inner.read(&mut buf[..max])?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// Discards up to max_size bytes the client sends within timeout or until it closes the
+/// connection. Closing a connection with unread data resets it, the client may then lose the
+/// response written before. Returns the number of bytes discarded
+pub(crate) fn discard_input(
+    stream: &TcpStream,
+    max_size: u64,
+    timeout: Duration,
+) -> io::Result<u64> {
+    let mut reader = DeadlineReader {
+        stream,
+        deadline: Instant::now() + timeout,
+    };
+    io::copy(&mut (&mut reader).take(max_size), &mut io::sink())
+}
+
+/// Reads from a stream until a deadline, every read may only wait for the time that is left
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_too_large(result: io::Result<Option<Body>>) -> bool {
+        result.is_err_and(|err| err.get_ref().is_some_and(|inner| inner.is::<BodyTooLarge>()))
+    }
+
+    #[test]
+    fn declared_length_over_the_limit_is_rejected_before_reading() {
+        let headers = [(String::from("Content-Length"), String::from("5"))];
+        assert!(is_too_large(read_body(&mut &b""[..], &headers, 4)));
+        let body = read_body(&mut &b"hello"[..], &headers, 5).unwrap().unwrap();
+        assert_eq!(body.data, b"hello");
+    }
+
+    #[test]
+    fn invalid_content_length_is_rejected() {
+        for length in ["abc", "5, 5", "-1", "+5", ""] {
+            let headers = [(String::from("Content-Length"), String::from(length))];
+            let Err(err) = read_body(&mut &b"hello"[..], &headers, 1024) else {
+                panic!("Content-Length {length:?} was accepted");
+            };
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(body_reader(&b"hello"[..], &headers).is_err());
+        }
+        let headers = [
+            (String::from("Content-Length"), String::from("5")),
+            (String::from("Content-Length"), String::from("4")),
+        ];
+        assert!(content_length(&headers).is_err());
+        let headers = [
+            (String::from("Content-Length"), String::from("5")),
+            (String::from("content-length"), String::from(" 5")),
+        ];
+        assert_eq!(content_length(&headers).unwrap(), Some(5));
+    }
+
+    #[test]
+    fn header_lines_and_count_are_bounded() {
+        let head = b"Server: test\r\nX-Long: 0123456789\r\n\r\n";
+        assert_eq!(read_headers(&mut &head[..], 2, 18).unwrap().len(), 2);
+        let Err(err) = read_headers(&mut &head[..], 1, 18) else {
+            panic!("more headers than allowed were accepted");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let Err(err) = read_headers(&mut &head[..], 2, 17) else {
+            panic!("a line longer than allowed was accepted");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn malformed_trailer_is_rejected_as_in_the_head() {
+        let headers = [(String::from("Transfer-Encoding"), String::from("chunked"))];
+        for trailer in ["X-Checksum 900150983cd24fb0", " folded", "X-Checksum : 1"] {
+            let chunked = format!("3\r\nabc\r\n0\r\n{trailer}\r\n\r\n");
+            let Err(err) = read_body(&mut chunked.as_bytes(), &headers, 1024) else {
+                panic!("trailer {trailer:?} was accepted");
+            };
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn chunked_has_to_be_the_only_and_final_coding() {
+        let coded = |codings: &[&str]| {
+            let headers: Vec<_> = codings
+                .iter()
+                .map(|coding| (String::from("Transfer-Encoding"), coding.to_string()))
+                .collect();
+            is_chunked(&headers).map_err(|err| err.kind())
+        };
+        assert_eq!(coded(&[]), Ok(false));
+        assert_eq!(coded(&["Chunked"]), Ok(true));
+        assert_eq!(coded(&[" chunked ,"]), Ok(true));
+        assert_eq!(coded(&["gzip, chunked"]), Err(io::ErrorKind::Unsupported));
+        assert_eq!(coded(&["gzip", "chunked"]), Err(io::ErrorKind::Unsupported));
+        let invalid: [&[&str]; 5] = [
+            &["chunked, gzip"],
+            &["gzip"],
+            &[""],
+            &["xchunked"],
+            &["chunked", "chunked"],
+        ];
+        for invalid in invalid {
+            assert_eq!(coded(invalid), Err(io::ErrorKind::InvalidData), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn chunked_body_is_cut_off_past_the_limit() {
+        let headers = [(String::from("Transfer-Encoding"), String::from("chunked"))];
+        let chunked = b"3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+        assert!(is_too_large(read_body(&mut &chunked[..], &headers, 4)));
+        let body = read_body(&mut &chunked[..], &headers, 5).unwrap().unwrap();
+        assert_eq!(body.data, b"abcde");
+    }
+
+    #[test]
+    fn chunk_data_has_to_end_at_its_size() {
+        let headers = [(String::from("Transfer-Encoding"), String::from("chunked"))];
+        let chunked = b"5\r\nhelloXYZ\r\n0\r\n\r\n";
+        let Err(err) = read_body(&mut &chunked[..], &headers, 1024) else {
+            panic!("chunk longer than its size was accepted");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn only_declared_trailers_are_kept() {
+        let headers = [
+            (String::from("Transfer-Encoding"), String::from("chunked")),
+            (String::from("Trailer"), String::from("X-Checksum, Content-Length")),
+        ];
+        let chunked = b"3\r\nabc\r\n0\r\n\
+            X-Checksum: 900150983cd24fb0\r\nContent-Length: 1\r\nX-Other: 1\r\n\r\n";
+        let body = read_body(&mut &chunked[..], &headers, 1024).unwrap().unwrap();
+        assert_eq!(body.trailers.len(), 3);
+        assert_eq!(
+            declared_trailers(&headers, body.trailers),
+            vec![(String::from("X-Checksum"), String::from("900150983cd24fb0"))]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::{io, net::IpAddr, time::Duration};
+
+use http_base::http::http_structs::{HttpData, HttpHeaders, HttpRequest, HttpResponse};
+
+use super::{
+    client,
+    request::HttpRequestExt,
+    response::{MAX_UPSTREAM_BODY, UPSTREAM_READ_TIMEOUT},
+};
+
+/// Headers that only apply to a single connection and are never forwarded. Content-Length is
+/// computed again from the forwarded body
+const HOP_BY_HOP: [&str; 9] = [
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Authenticate",
+    "Proxy-Authorization",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+    "Content-Length",
+];
+
+/// Forwards a request to the upstream server at addr, e.g. `127.0.0.1:8080`, and returns its
+/// response. Method, path, headers and body are copied, peer is appended to `X-Forwarded-For`,
+/// usually the address the request was received from, see [`HttpRequestExt::peer_ip`]. Hop-by-hop headers, including
+/// the ones listed in `Connection`, are dropped in both directions. `Expect` is not forwarded,
+/// the body has been read already, so the upstream is never asked to continue. The upstream
+/// connection is closed after one request. The response is buffered in memory, bodies over
+/// 10 MiB and upstream statuses unknown to HttpStatus are an `io::ErrorKind::InvalidData` error,
+/// reads blocking for more than 30 seconds an `io::ErrorKind::WouldBlock` or `TimedOut` error.
+/// Use `forward_with_limits` for other bounds, `handler_timeout` bounds the whole exchange
+/// Example:
+/// ```rust
+/// use http_serv::{HttpRequest, HttpRequestExt, HttpResponse, HttpStatus, http_server::{proxy, server::HttpServer}};
+///
+/// # #[cfg(not(feature = "threading"))]
+/// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+/// # #[cfg(feature = "threading")]
+/// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+/// server.get("/api/*".to_owned(), Box::new(|request: &HttpRequest| {
+///     proxy::forward(request, request.peer_ip(), "127.0.0.1:8080").unwrap_or_else(|_| {
+///         HttpResponse::new(String::from("1.1"), HttpStatus::BadGateway, None, None)
+///     })
+/// }));
+/// ```
+/// The request as the upstream receives it:
+/// ```rust
+/// use std::{io::{BufRead, BufReader, Write}, net::TcpListener, thread};
+/// use http_serv::{HttpMethod, HttpStatus, example_request, http_server::proxy};
+///
+/// let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let addr = upstream.local_addr().unwrap().to_string();
+/// let received = thread::spawn(move || {
+///     let (mut stream, _) = upstream.accept().unwrap();
+///     let head: Vec<String> = BufReader::new(&stream)
+///         .lines()
+///         .map(Result::unwrap)
+///         .take_while(|line| !line.is_empty())
+///         .collect();
+///     write!(stream, "HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+///     head
+/// });
+/// let request = example_request(
+///     HttpMethod::GET,
+///     "/upload",
+///     &[
+///         ("Expect", "100-continue"),
+///         ("Connection", "X-Session"),
+///         ("X-Session", "secret"),
+///         ("X-Forwarded-For", "198.51.100.4"),
+///         ("X-Forwarded-For", "203.0.113.9"),
+///     ],
+/// );
+/// let peer = "10.0.0.2".parse().ok();
+/// assert_eq!(proxy::forward(&request, peer, &addr).unwrap().status, HttpStatus::NoContent);
+/// assert_eq!(received.join().unwrap(), vec![
+///     "GET /upload HTTP/1.1",
+///     "X-Forwarded-For: 198.51.100.4, 203.0.113.9, 10.0.0.2",
+///     "Connection: close",
+/// ]);
+/// ```
+pub fn forward(request: &HttpRequest, peer: Option<IpAddr>, addr: &str) -> io::Result<HttpResponse> {
+    forward_with_limits(request, peer, addr, MAX_UPSTREAM_BODY, UPSTREAM_READ_TIMEOUT)
+}
+
+/// Forwards a request like `forward`, with max_size as the largest response body buffered and
+/// read_timeout as the longest time a single read from the upstream may block
+/// Example:
+/// ```rust
+/// use std::time::Duration;
+/// use http_serv::{HttpRequest, HttpRequestExt, HttpResponse, HttpStatus, http_server::{proxy, server::HttpServer}};
+///
+/// # #[cfg(not(feature = "threading"))]
+/// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+/// # #[cfg(feature = "threading")]
+/// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+/// server.get("/reports/*".to_owned(), |request: &HttpRequest| {
+///     let (max_size, read_timeout) = (100 * 1024 * 1024, Duration::from_secs(120));
+///     proxy::forward_with_limits(request, request.peer_ip(), "127.0.0.1:8080", max_size, read_timeout)
+///         .unwrap_or_else(|_| HttpResponse::new(String::from("1.1"), HttpStatus::BadGateway, None, None))
+/// });
+/// ```
+pub fn forward_with_limits(
+    request: &HttpRequest,
+    peer: Option<IpAddr>,
+    addr: &str,
+    max_size: u64,
+    read_timeout: Duration,
+) -> io::Result<HttpResponse> {
+    let body = request.data.clone().map(HttpData::Bytes);
+    let mut response = client::send(
+        addr,
+        &upstream_headers(request, peer),
+        body,
+        max_size,
+        read_timeout,
+    )?;
+    // without a body the upstream Content-Length is the only one, e.g. for HEAD requests
+    let has_body = response.data.is_some();
+    if let Some(headers) = &mut response.extra_headers {
+        let options = connection_options(headers);
+        headers.retain(|(key, _)| {
+            let hop_by_hop = is_hop_by_hop(key) || is_connection_option(&options, key);
+            !hop_by_hop || (!has_body && key.eq_ignore_ascii_case("Content-Length"))
+        });
+    }
+    Ok(response)
+}
+
+/// Builds the request line and headers sent upstream
+fn upstream_headers(request: &HttpRequest, peer: Option<IpAddr>) -> HttpHeaders {
+    let headers = &request.http_headers;
+    let options = connection_options(&headers.extra_headers);
+    let mut extra_headers: Vec<(String, String)> = headers
+        .extra_headers
+        .iter()
+        .filter(|(key, _)| {
+            !is_hop_by_hop(key)
+                && !is_connection_option(&options, key)
+                && !key.eq_ignore_ascii_case("X-Forwarded-For")
+                && !key.eq_ignore_ascii_case("Expect")
+        })
+        .cloned()
+        .collect();
+    // repeated headers form one chain, proxies append the address they received the request from
+    let peer = peer.map(|ip| ip.to_string());
+    let mut forwarded_for = request.header_values("X-Forwarded-For");
+    forwarded_for.extend(peer.as_deref());
+    if !forwarded_for.is_empty() {
+        extra_headers.push((String::from("X-Forwarded-For"), forwarded_for.join(", ")));
+    }
+    if let Some(data) = &request.data {
+        extra_headers.push((String::from("Content-Length"), data.len().to_string()));
+    }
+    extra_headers.push((String::from("Connection"), String::from("close")));
+    HttpHeaders {
+        method: headers.method.clone(),
+        path: headers.path.clone(),
+        protocol: String::from("HTTP/1.1"),
+        extra_headers,
+    }
+}
+
+/// Returns the header names listed in the Connection header, which apply to the connection only
+fn connection_options(headers: &[(String, String)]) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case("Connection"))
+        .flat_map(|(_, value)| value.split(','))
+        .map(str::trim)
+        .filter(|option| !option.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn is_connection_option(options: &[String], name: &str) -> bool {
+    options.iter().any(|option| option.eq_ignore_ascii_case(name))
+}
+
+fn is_hop_by_hop(name: &str) -> bool {
+    HOP_BY_HOP
+        .iter()
+        .any(|header| header.eq_ignore_ascii_case(name))
+}
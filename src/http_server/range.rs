@@ -0,0 +1,143 @@
+use http_base::http::http_structs::{HttpData, HttpMethod, HttpRequest, HttpResponse, HttpStatus};
+
+use super::{
+    date::DateTime, request::HttpRequestExt, response::HttpResponseExt, status::HttpStatusExt,
+};
+
+/// Outcome of evaluating a Range header against a body length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ByteRange {
+    /// No usable range, the full body is sent
+    Full,
+    /// Inclusive byte range to send
+    Partial { start: u64, end: u64 },
+    /// The range lies outside the body
+    Unsatisfiable,
+}
+
+/// Parses a single `bytes=` range. Malformed and multi range headers are ignored as permitted by RFC 9110
+pub(crate) fn parse_range(header: &str, total: u64) -> ByteRange {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=500-999
+        (Ok(start), Ok(end)) if start <= end => match start < total {
+            true => ByteRange::Partial {
+                start,
+                end: end.min(total - 1),
+            },
+            false => ByteRange::Unsatisfiable,
+        },
+        // bytes=500-
+        (Ok(start), Err(_)) if end.is_empty() => match start < total {
+            true => ByteRange::Partial {
+                start,
+                end: total - 1,
+            },
+            false => ByteRange::Unsatisfiable,
+        },
+        // bytes=-500, the last 500 bytes
+        (Err(_), Ok(suffix)) if start.is_empty() => match suffix > 0 && total > 0 {
+            true => ByteRange::Partial {
+                start: total.saturating_sub(suffix),
+                end: total - 1,
+            },
+            false => ByteRange::Unsatisfiable,
+        },
+        _ => ByteRange::Full,
+    }
+}
+
+/// Checks an If-Range header against the response validators. Entity tags have to match strongly,
+/// dates have to equal Last-Modified exactly, anything else means the resource changed
+fn if_range_matches(header: &str, response: &HttpResponse) -> bool {
+    let header = header.trim();
+    if header.starts_with('"') {
+        return response
+            .header("ETag")
+            .is_some_and(|etag| !etag.starts_with("W/") && etag.trim() == header);
+    }
+    if header.starts_with("W/") {
+        return false;
+    }
+    match (
+        DateTime::parse_http_date(header),
+        response
+            .header("Last-Modified")
+            .and_then(DateTime::parse_http_date),
+    ) {
+        (Some(date), Some(last_modified)) => {
+            date.to_system_time() == last_modified.to_system_time()
+        }
+        _ => false,
+    }
+}
+
+/// Narrows a full 200 response to a GET request down to the requested range.
+/// Byte bodies are sliced directly, for streams the bytes to skip and the length to send are returned.
+/// Streams are only narrowed if their length is known from a Content-Length header. With an
+/// If-Range header the range is only applied if the resource did not change. Responses that
+/// could be narrowed advertise `Accept-Ranges: bytes`, unless the handler set Accept-Ranges itself
+pub(crate) fn apply_range(
+    request: &HttpRequest,
+    response: &mut HttpResponse,
+) -> Option<(u64, u64)> {
+    let method = &request.http_headers.method;
+    if !matches!(method, HttpMethod::GET | HttpMethod::HEAD) || response.status.code() != 200 {
+        return None;
+    }
+    let total = match &response.data {
+        Some(HttpData::Bytes(bytes)) => bytes.len() as u64,
+        Some(HttpData::Stream(_)) => response.header("Content-Length")?.trim().parse().ok()?,
+        None => return None,
+    };
+    match response.header("Accept-Ranges") {
+        // e.g. `none` for generated bodies that differ between requests
+        Some(accept_ranges) if !accept_ranges.trim().eq_ignore_ascii_case("bytes") => return None,
+        Some(_) => {}
+        None => response.set_header("Accept-Ranges", "bytes"),
+    }
+    // HEAD requests only learn that ranges are supported
+    if *method == HttpMethod::HEAD {
+        return None;
+    }
+    let header = request.header("Range")?;
+    if request
+        .header("If-Range")
+        .is_some_and(|if_range| !if_range_matches(if_range, response))
+    {
+        return None;
+    }
+
+    match parse_range(header, total) {
+        ByteRange::Full => None,
+        ByteRange::Partial { start, end } => {
+            response.status = HttpStatus::PartialContent;
+            response.set_header("Content-Range", &format!("bytes {start}-{end}/{total}"));
+            match &mut response.data {
+                Some(HttpData::Bytes(bytes)) => {
+                    *bytes = bytes[start as usize..=end as usize].to_vec();
+                    None
+                }
+                _ => {
+                    response.set_header("Content-Length", &(end - start + 1).to_string());
+                    Some((start, end - start + 1))
+                }
+            }
+        }
+        ByteRange::Unsatisfiable => {
+            response.status = HttpStatus::RangeNotSatisfiable;
+            response.data = None;
+            response.set_header("Content-Range", &format!("bytes */{total}"));
+            response.set_header("Content-Length", "0");
+            None
+        }
+    }
+}
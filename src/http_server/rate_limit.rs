@@ -0,0 +1,130 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Amount of tracked clients after which refilled buckets are evicted
+const EVICT_THRESHOLD: usize = 4096;
+/// Shortest time between two evictions, each one walks every bucket
+const MIN_EVICT_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+struct Buckets {
+    clients: HashMap<IpAddr, Bucket>,
+    last_evict: Instant,
+}
+
+/// Token bucket rate limiter keyed by client ip
+pub(crate) struct RateLimiter {
+    requests_per_sec: f64,
+    burst: f64,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_sec: u32, burst: u32) -> Self {
+        Self {
+            requests_per_sec: f64::from(requests_per_sec.max(1)),
+            burst: f64::from(burst.max(1)),
+            buckets: Mutex::new(Buckets {
+                clients: HashMap::new(),
+                last_evict: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token for the client, returns how long to wait for the next token if there is none left
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        // a poisoned lock only means another request panicked while holding it, the counts are still usable
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // buckets which would be full again carry no state. Evicting walks every bucket, so it
+        // runs at most once per refill time, even if many clients are active at once
+        let refill_time =
+            Duration::from_secs_f64(self.burst / self.requests_per_sec).max(MIN_EVICT_INTERVAL);
+        if buckets.clients.len() >= EVICT_THRESHOLD
+            && now.duration_since(buckets.last_evict) >= refill_time
+        {
+            buckets
+                .clients
+                .retain(|_, bucket| now.duration_since(bucket.last_refill) < refill_time);
+            buckets.last_evict = now;
+        }
+
+        let bucket = buckets.clients.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.requests_per_sec).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.requests_per_sec,
+            ))
+        }
+    }
+}
+
+/// Spaces accepted connections evenly at a fixed rate per second
+pub(crate) struct AcceptThrottle {
+    interval: Duration,
+    last_accept: Option<Instant>,
+}
+
+impl AcceptThrottle {
+    pub fn new(per_sec: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / per_sec.max(1),
+            last_accept: None,
+        }
+    }
+
+    /// Records a connection accepted at now, returns how long to wait before handling it
+    pub fn delay(&mut self, now: Instant) -> Duration {
+        let delay = match self.last_accept {
+            Some(last) => (last + self.interval).saturating_duration_since(now),
+            None => Duration::ZERO,
+        };
+        self.last_accept = Some(now + delay);
+        delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_throttle_spaces_connections() {
+        let start = Instant::now();
+        let mut throttle = AcceptThrottle::new(10);
+        assert_eq!(throttle.delay(start), Duration::ZERO);
+        // a burst waits one interval per connection
+        assert_eq!(throttle.delay(start), Duration::from_millis(100));
+        assert_eq!(
+            throttle.delay(start + Duration::from_millis(10)),
+            Duration::from_millis(190)
+        );
+        // after a quiet period connections are handled right away again
+        assert_eq!(throttle.delay(start + Duration::from_secs(1)), Duration::ZERO);
+        assert_eq!(
+            throttle.delay(start + Duration::from_millis(1050)),
+            Duration::from_millis(50)
+        );
+    }
+}
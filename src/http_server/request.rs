@@ -0,0 +1,700 @@
+use std::{
+    cell::Cell,
+    error::Error,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    string::FromUtf8Error,
+};
+
+use http_base::http::http_structs::{HttpHeaders, HttpMethod, HttpRequest};
+#[cfg(feature = "json")]
+use http_base::http::http_structs::HttpStatus;
+#[cfg(any(feature = "serde", feature = "json"))]
+use serde::de::DeserializeOwned;
+
+/// The connection a request was received on. HttpRequest has no fields for it, the server
+/// records it for the thread running the handler instead, see `HttpRequestExt::connection`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// Address of the tcp peer, None if it could not be determined
+    pub peer: Option<SocketAddr>,
+    /// Address the connection was accepted on, None if it could not be determined
+    pub local: Option<SocketAddr>,
+}
+
+thread_local! {
+    /// Connection of the request served on this thread, set while its handler runs
+    static CONNECTION: Cell<Option<ConnectionInfo>> = const { Cell::new(None) };
+}
+
+/// Records connection as the one served on this thread while f runs
+pub(crate) fn with_connection<T>(connection: ConnectionInfo, f: impl FnOnce() -> T) -> T {
+    /// Restores the previous connection, also if f panics
+    struct Restore(Option<ConnectionInfo>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CONNECTION.with(|current| current.set(self.0));
+        }
+    }
+
+    let _restore = Restore(CONNECTION.with(|current| current.replace(Some(connection))));
+    f()
+}
+
+/// Builds an HTTP/1.1 request from method, path and headers without body or params, as the
+/// examples need one without going through the server
+#[doc(hidden)]
+pub fn example_request(method: HttpMethod, path: &str, headers: &[(&str, &str)]) -> HttpRequest {
+    HttpRequest {
+        http_headers: HttpHeaders {
+            method,
+            path: path.to_owned(),
+            protocol: String::from("HTTP/1.1"),
+            extra_headers: headers
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        },
+        data: None,
+        route_params: None,
+        query_params: None,
+    }
+}
+
+/// Convenience accessors for HttpRequests
+pub trait HttpRequestExt {
+    /// Returns the value of the first request header matching name, ignoring case
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpRequest, HttpRequestExt, HttpResponse};
+    ///
+    /// let handler = |request: &HttpRequest| {
+    ///     let _agent = request.header("user-agent").unwrap_or("unknown");
+    ///     HttpResponse::default()
+    /// };
+    /// ```
+    fn header(&self, name: &str) -> Option<&str>;
+
+    /// Returns the values of all request headers matching name in the order they were received,
+    /// ignoring case, for headers that may repeat like `X-Forwarded-For`
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequestExt, example_request};
+    ///
+    /// let request = example_request(
+    ///     HttpMethod::GET,
+    ///     "/",
+    ///     &[("Cookie", "a=1"), ("Accept", "*/*"), ("cookie", "b=2")],
+    /// );
+    /// assert_eq!(request.header_values("Cookie"), vec!["a=1", "b=2"]);
+    /// ```
+    fn header_values(&self, name: &str) -> Vec<&str>;
+
+    /// Returns the media type of the Content-Type header without parameters, e.g. `text/html`
+    /// for `text/html; charset=utf-8`
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpRequest, HttpRequestExt, HttpResponse};
+    ///
+    /// let handler = |request: &HttpRequest| {
+    ///     if request.content_type() == Some("application/json") {
+    ///         // parse body
+    ///     }
+    ///     HttpResponse::default()
+    /// };
+    /// ```
+    fn content_type(&self) -> Option<&str>;
+
+    /// Returns a parameter of the Content-Type header by name, e.g. the `boundary` of multipart bodies.
+    /// Quoted values are returned without the quotes
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpRequest, HttpRequestExt, HttpResponse};
+    ///
+    /// let handler = |request: &HttpRequest| {
+    ///     let _charset = request.content_type_param("charset").unwrap_or("utf-8");
+    ///     HttpResponse::default()
+    /// };
+    /// ```
+    fn content_type_param(&self, name: &str) -> Option<&str>;
+
+    /// Checks whether the Accept header allows media_type, honoring `type/*` and `*/*` ranges.
+    /// The most specific matching range decides, so `text/*;q=0` rejects `text/html` unless
+    /// `text/html` is listed itself. Requests without Accept header accept everything
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequestExt, example_request};
+    ///
+    /// let request = example_request(HttpMethod::GET, "/", &[("Accept", "text/*, image/png;q=0")]);
+    /// assert!(request.accepts("text/html"));
+    /// assert!(!request.accepts("image/png"));
+    /// assert!(!request.accepts("application/json"));
+    /// ```
+    fn accepts(&self, media_type: &str) -> bool;
+
+    /// Picks the offered media type the client prefers according to the quality values of the
+    /// Accept header. Ties are won by the type offered first, None is returned if the client
+    /// accepts none of them
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequestExt, example_request};
+    ///
+    /// let request = example_request(
+    ///     HttpMethod::GET,
+    ///     "/",
+    ///     &[("Accept", "text/html;q=0.8, application/json")],
+    /// );
+    /// assert_eq!(request.preferred_media_type(&["text/html", "application/json"]), Some("application/json"));
+    /// assert_eq!(request.preferred_media_type(&["image/png"]), None);
+    /// ```
+    fn preferred_media_type<'a>(&self, offered: &[&'a str]) -> Option<&'a str>;
+
+    /// Decodes the body as text in the charset declared by the Content-Type header, UTF-8 if none
+    /// is declared. UTF-8 and ISO-8859-1 (Latin-1) are supported, a missing body yields an empty string
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequestExt, example_request};
+    ///
+    /// let mut request = example_request(
+    ///     HttpMethod::POST,
+    ///     "/",
+    ///     &[("Content-Type", "text/plain; charset=iso-8859-1")],
+    /// );
+    /// request.data = Some(vec![b'c', b'a', b'f', 0xe9]);
+    /// assert_eq!(request.body_string_with_charset().unwrap(), "café");
+    /// ```
+    fn body_string_with_charset(&self) -> Result<String, CharsetError>;
+
+    /// Sets a request header, replacing any existing header of the same name
+    fn set_header(&mut self, name: &str, value: &str);
+
+    /// Removes all request headers matching name, ignoring case
+    fn remove_header(&mut self, name: &str);
+
+    /// Returns the ip of the client, the tcp peer unless forwarding headers name another one. The
+    /// server only keeps `X-Forwarded-For` / `X-Real-IP` sent by the client if `trust_proxy` is
+    /// enabled, in which case the last `X-Forwarded-For` entry wins, also across repeated header
+    /// lines. It was appended by the trusted proxy, entries before it are sent by the client and
+    /// can be spoofed. Without forwarding headers the tcp peer is taken from `peer_ip`, which
+    /// belongs to the handler running on this thread, see `connection`
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequestExt, example_request};
+    ///
+    /// let request = example_request(
+    ///     HttpMethod::GET,
+    ///     "/",
+    ///     &[("X-Forwarded-For", "1.2.3.4, 203.0.113.9")],
+    /// );
+    /// // 1.2.3.4 is whatever the client claimed, the proxy appended the address it saw
+    /// assert_eq!(request.real_ip(), Some("203.0.113.9".parse().unwrap()));
+    ///
+    /// // a proxy adding its own line instead of appending to the client's one wins as well
+    /// let mut request = request;
+    /// request.http_headers.extra_headers = vec![
+    ///     (String::from("X-Forwarded-For"), String::from("1.2.3.4")),
+    ///     (String::from("X-Forwarded-For"), String::from("203.0.113.9")),
+    /// ];
+    /// assert_eq!(request.real_ip(), Some("203.0.113.9".parse().unwrap()));
+    /// ```
+    fn real_ip(&self) -> Option<IpAddr>;
+
+    /// Returns the connection the request was received on. The server records it next to the
+    /// request for the thread running the handler, so this is None for requests built by hand.
+    /// Its peer is None if the server could not determine the address, the request is served anyway.
+    /// The connection is looked up for the current thread, not stored in the request: any request
+    /// a handler builds, e.g. for `HttpClient` or `proxy`, reports the incoming connection while
+    /// the handler runs, and a request moved to another thread reports None there
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpRequest, HttpRequestExt, HttpResponse};
+    ///
+    /// let handler = |request: &HttpRequest| {
+    ///     match request.connection() {
+    ///         Some(connection) if connection.peer.is_none() => eprintln!("peer address unknown"),
+    ///         Some(_) => {}
+    ///         None => eprintln!("not received by the server"),
+    ///     }
+    ///     HttpResponse::default()
+    /// };
+    /// ```
+    fn connection(&self) -> Option<ConnectionInfo>;
+
+    /// Returns the ip of the tcp peer the request was received from, which is the proxy's address
+    /// when the server runs behind one, see `real_ip` for the client. None if the peer is unknown
+    /// or the request has no recorded connection, see `connection` to tell the two apart. Like
+    /// `connection` it is the peer of the handler running on this thread, not of the request
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpRequest, HttpRequestExt, HttpResponse};
+    ///
+    /// let handler = |request: &HttpRequest| {
+    ///     let _peer = request.peer_ip();
+    ///     HttpResponse::default()
+    /// };
+    /// ```
+    fn peer_ip(&self) -> Option<IpAddr>;
+
+    /// Returns the scheme the client used, `https` or `http`. The server does not terminate TLS
+    /// itself, so this is `https` only if a trusted proxy sent `X-Forwarded-Proto: https`, see
+    /// `trust_proxy`. Of a chained or repeated header only the last entry, the one the proxy
+    /// appended, is used
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequestExt, example_request};
+    ///
+    /// let request = example_request(
+    ///     HttpMethod::GET,
+    ///     "/",
+    ///     &[("X-Forwarded-Proto", "https, http")],
+    /// );
+    /// // https is whatever the client claimed, the proxy received plain http
+    /// assert_eq!(request.scheme(), "http");
+    /// assert!(!request.is_secure());
+    /// ```
+    fn scheme(&self) -> &str;
+
+    /// Checks whether the client used https, see [`HttpRequestExt::scheme`]
+    fn is_secure(&self) -> bool {
+        self.scheme() == "https"
+    }
+
+    /// Returns the Host header, which HTTP/1.1 requests are required to send. HTTP/1.0 requests
+    /// may omit it, `url` then falls back to the address the connection was accepted on
+    fn host(&self) -> Option<&str>;
+
+    /// Returns the Host header split into host name and port. IPv6 hosts are returned without the
+    /// brackets, a missing or invalid port is returned as None
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequestExt, example_request};
+    ///
+    /// let mut request = example_request(HttpMethod::GET, "/", &[("Host", "example.com:8080")]);
+    /// assert_eq!(request.host_and_port(), Some(("example.com", Some(8080))));
+    ///
+    /// request.set_header("Host", "[::1]");
+    /// assert_eq!(request.host_and_port(), Some(("::1", None)));
+    /// ```
+    fn host_and_port(&self) -> Option<(&str, Option<u16>)>;
+
+    /// Returns the absolute url of the request from scheme, Host header, path and query, e.g. for
+    /// Location headers. Without Host header, as HTTP/1.0 allows, the address the connection was
+    /// accepted on is used, see `connection`, and `localhost` for requests built by hand
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequestExt, example_request};
+    ///
+    /// let request = example_request(
+    ///     HttpMethod::GET,
+    ///     "/users?page=2",
+    ///     &[("Host", "example.com:8080")],
+    /// );
+    /// assert_eq!(request.url(), "http://example.com:8080/users?page=2");
+    /// ```
+    fn url(&self) -> String;
+
+    /// Parses the route param with the given name, e.g. `id` for `/users/:id`. Returns None if the route has no
+    /// such param and the parse error if its value is not a valid T
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequestExt, example_request};
+    ///
+    /// let mut request = example_request(HttpMethod::GET, "/users/42?page=x", &[]);
+    /// request.route_params = Some(vec![(String::from("id"), String::from("42"))]);
+    /// request.query_params = Some(vec![(String::from("page"), String::from("x"))]);
+    /// assert_eq!(request.route_param_parsed::<u32>("id"), Some(Ok(42)));
+    /// assert!(request.query_param_parsed::<u32>("page").unwrap().is_err());
+    /// assert!(request.query_param_parsed::<u32>("limit").is_none());
+    /// ```
+    fn route_param_parsed<T: FromStr>(&self, name: &str) -> Option<Result<T, T::Err>>;
+
+    /// Parses the first query param with the given name, see [`HttpRequestExt::route_param_parsed`]
+    fn query_param_parsed<T: FromStr>(&self, name: &str) -> Option<Result<T, T::Err>>;
+
+    /// Deserializes the query string into T. Values are percent decoded, missing query strings are
+    /// treated as empty so structs with only optional fields still deserialize
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpRequest, HttpRequestExt, HttpResponse, HttpStatus};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Search {
+    ///     q: String,
+    ///     page: Option<u32>,
+    /// }
+    ///
+    /// let handler = |request: &HttpRequest| {
+    ///     let search = match request.query::<Search>() {
+    ///         Ok(search) => search,
+    ///         Err(_) => return HttpResponse::new(String::from("1.1"), HttpStatus::BadRequest, None, None),
+    ///     };
+    ///     HttpResponse::default()
+    /// };
+    /// ```
+    #[cfg(feature = "serde")]
+    fn query<T: DeserializeOwned>(&self) -> Result<T, QueryError>;
+
+    /// Deserializes the JSON body into T. The body must be present and the Content-Type must be
+    /// `application/json` or end in `+json`, use [`HttpRequestExt::json_unchecked`] for clients
+    /// that do not send one
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpRequest, HttpRequestExt, HttpResponse, HttpResponseExt, HttpStatus};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Deserialize, Serialize)]
+    /// struct User {
+    ///     name: String,
+    /// }
+    ///
+    /// let handler = |request: &HttpRequest| match request.json::<User>() {
+    ///     Ok(user) => HttpResponse::json_body(HttpStatus::Created, &user),
+    ///     Err(err) => HttpResponse::new(String::from("1.1"), err.status(), None, None),
+    /// };
+    /// ```
+    #[cfg(feature = "json")]
+    fn json<T: DeserializeOwned>(&self) -> Result<T, JsonError>;
+
+    /// Deserializes the JSON body into T without checking the Content-Type
+    #[cfg(feature = "json")]
+    fn json_unchecked<T: DeserializeOwned>(&self) -> Result<T, JsonError>;
+}
+
+impl HttpRequestExt for HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.http_headers
+            .extra_headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn header_values(&self, name: &str) -> Vec<&str> {
+        self.http_headers
+            .extra_headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    fn content_type(&self) -> Option<&str> {
+        self.header("Content-Type")?
+            .split(';')
+            .next()
+            .map(str::trim)
+            .filter(|media_type| !media_type.is_empty())
+    }
+
+    fn content_type_param(&self, name: &str) -> Option<&str> {
+        self.header("Content-Type")?
+            .split(';')
+            .skip(1)
+            .filter_map(|param| param.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim().trim_matches('"'))
+    }
+
+    fn accepts(&self, media_type: &str) -> bool {
+        accept_quality(self.header("Accept"), media_type) > 0.0
+    }
+
+    fn preferred_media_type<'a>(&self, offered: &[&'a str]) -> Option<&'a str> {
+        let accept = self.header("Accept");
+        let mut preferred = None;
+        let mut best = 0.0;
+        for media_type in offered {
+            let quality = accept_quality(accept, media_type);
+            if quality > best {
+                preferred = Some(*media_type);
+                best = quality;
+            }
+        }
+        preferred
+    }
+
+    fn body_string_with_charset(&self) -> Result<String, CharsetError> {
+        let body = self.data.as_deref().unwrap_or_default();
+        let charset = self
+            .content_type_param("charset")
+            .unwrap_or("utf-8")
+            .to_ascii_lowercase();
+        match charset.as_str() {
+            "utf-8" | "utf8" => String::from_utf8(body.to_vec()).map_err(CharsetError::Invalid),
+            // every Latin-1 byte is the code point of the same value
+            "iso-8859-1" | "latin1" => Ok(body.iter().map(|byte| char::from(*byte)).collect()),
+            _ => Err(CharsetError::Unsupported(charset)),
+        }
+    }
+
+    fn set_header(&mut self, name: &str, value: &str) {
+        self.remove_header(name);
+        self.http_headers
+            .extra_headers
+            .push((name.to_owned(), value.to_owned()));
+    }
+
+    fn remove_header(&mut self, name: &str) {
+        self.http_headers
+            .extra_headers
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    }
+
+    fn real_ip(&self) -> Option<IpAddr> {
+        // proxies append the address they received the request from, only the last one is trusted
+        // repeated header lines form one list, the proxy appended its entry to the last line
+        let forwarded_for = self
+            .header_values("X-Forwarded-For")
+            .last()
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+        forwarded_for
+            .or_else(|| self.header("X-Real-IP")?.trim().parse().ok())
+            .or_else(|| self.peer_ip())
+    }
+
+    fn connection(&self) -> Option<ConnectionInfo> {
+        CONNECTION.with(Cell::get)
+    }
+
+    fn peer_ip(&self) -> Option<IpAddr> {
+        self.connection()?.peer.map(|peer| peer.ip())
+    }
+
+    fn scheme(&self) -> &str {
+        // proxies append the scheme they received, as for real_ip only the last one is trusted
+        let forwarded_proto = self
+            .header_values("X-Forwarded-Proto")
+            .last()
+            .and_then(|value| value.rsplit(',').next());
+        match forwarded_proto {
+            Some(proto) if proto.trim().eq_ignore_ascii_case("https") => "https",
+            _ => "http",
+        }
+    }
+
+    fn host(&self) -> Option<&str> {
+        self.header("Host").map(str::trim)
+    }
+
+    fn url(&self) -> String {
+        let path = &self.http_headers.path;
+        // requests in absolute form already carry the url
+        if path.starts_with("http://") || path.starts_with("https://") {
+            return path.clone();
+        }
+        let local = self.connection().and_then(|connection| connection.local);
+        let host = match (self.host(), local) {
+            (Some(host), _) => host.to_owned(),
+            (None, Some(local)) => local.to_string(),
+            (None, None) => String::from("localhost"),
+        };
+        format!("{}://{}{}", self.scheme(), host, path)
+    }
+
+    fn route_param_parsed<T: FromStr>(&self, name: &str) -> Option<Result<T, T::Err>> {
+        let (_, value) = self
+            .route_params
+            .iter()
+            .flatten()
+            .find(|(key, _)| key == name)?;
+        Some(value.parse())
+    }
+
+    fn query_param_parsed<T: FromStr>(&self, name: &str) -> Option<Result<T, T::Err>> {
+        let (_, value) = self
+            .query_params
+            .iter()
+            .flatten()
+            .find(|(key, _)| key == name)?;
+        Some(value.parse())
+    }
+
+    fn host_and_port(&self) -> Option<(&str, Option<u16>)> {
+        split_host_port(self.host()?)
+    }
+
+    #[cfg(feature = "serde")]
+    fn query<T: DeserializeOwned>(&self) -> Result<T, QueryError> {
+        let query = self
+            .http_headers
+            .path
+            .split_once('?')
+            .map(|(_, query)| query)
+            .unwrap_or_default();
+        serde_urlencoded::from_str(query).map_err(QueryError)
+    }
+
+    #[cfg(feature = "json")]
+    fn json<T: DeserializeOwned>(&self) -> Result<T, JsonError> {
+        let is_json = self.content_type().is_some_and(|content_type| {
+            content_type.eq_ignore_ascii_case("application/json")
+                || content_type.to_ascii_lowercase().ends_with("+json")
+        });
+        if !is_json {
+            return Err(JsonError::UnsupportedContentType(
+                self.content_type().map(str::to_owned),
+            ));
+        }
+        self.json_unchecked()
+    }
+
+    #[cfg(feature = "json")]
+    fn json_unchecked<T: DeserializeOwned>(&self) -> Result<T, JsonError> {
+        let body = self.data.as_deref().ok_or(JsonError::MissingBody)?;
+        serde_json::from_slice(body).map_err(JsonError::Invalid)
+    }
+}
+
+/// Splits a Host header value into host name and port, the port is None if missing or invalid
+pub(crate) fn split_host_port(host: &str) -> Option<(&str, Option<u16>)> {
+    // bracketed IPv6 literals contain colons themselves
+    if let Some(rest) = host.strip_prefix('[') {
+        let (name, port) = rest.split_once(']')?;
+        return Some((
+            name,
+            port.strip_prefix(':').and_then(|port| port.parse().ok()),
+        ));
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) => Some((name, port.parse().ok())),
+        None => Some((host, None)),
+    }
+}
+
+/// Returns the quality the Accept header assigns to media_type, taken from the most specific
+/// matching range. A missing header accepts everything with quality 1
+fn accept_quality(accept: Option<&str>, media_type: &str) -> f32 {
+    let Some(accept) = accept else {
+        return 1.0;
+    };
+    let media_type = media_type.split(';').next().unwrap_or_default().trim();
+    let (main_type, sub_type) = media_type.split_once('/').unwrap_or((media_type, ""));
+    let mut best: Option<(u8, f32)> = None;
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let range = params.next().unwrap_or_default().trim();
+        let Some((range_main, range_sub)) = range.split_once('/') else {
+            continue;
+        };
+        // exact ranges beat type/* ranges, which beat */*
+        let specificity = match (range_main, range_sub) {
+            ("*", "*") => 0,
+            (range_main, "*") if range_main.eq_ignore_ascii_case(main_type) => 1,
+            (range_main, range_sub)
+                if range_main.eq_ignore_ascii_case(main_type)
+                    && range_sub.eq_ignore_ascii_case(sub_type) =>
+            {
+                2
+            }
+            _ => continue,
+        };
+        let quality = params
+            .filter_map(|param| param.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("q"))
+            .map_or(Some(1.0), |(_, value)| value.trim().parse::<f32>().ok())
+            .unwrap_or(0.0)
+            .clamp(0.0, 1.0);
+        if best.map_or(true, |(best_specificity, _)| specificity > best_specificity) {
+            best = Some((specificity, quality));
+        }
+    }
+    best.map_or(0.0, |(_, quality)| quality)
+}
+
+/// Error returned when the body of a request cannot be decoded as text
+#[derive(Debug)]
+pub enum CharsetError {
+    /// The declared charset is not supported
+    Unsupported(String),
+    /// The body is not valid UTF-8
+    Invalid(FromUtf8Error),
+}
+
+impl fmt::Display for CharsetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CharsetError::Unsupported(charset) => write!(f, "unsupported charset {charset}"),
+            CharsetError::Invalid(err) => write!(f, "invalid utf-8 body: {err}"),
+        }
+    }
+}
+
+impl Error for CharsetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CharsetError::Invalid(err) => Some(err),
+            CharsetError::Unsupported(_) => None,
+        }
+    }
+}
+
+/// Error returned when the body of a request cannot be deserialized as JSON
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub enum JsonError {
+    /// The request has no body
+    MissingBody,
+    /// The Content-Type is missing or not a JSON media type
+    UnsupportedContentType(Option<String>),
+    /// The body is not valid JSON or does not match the requested type
+    Invalid(serde_json::Error),
+}
+
+#[cfg(feature = "json")]
+impl JsonError {
+    /// Returns the status to answer the request with, 415 for a wrong Content-Type, 400 otherwise
+    pub fn status(&self) -> HttpStatus {
+        match self {
+            JsonError::UnsupportedContentType(_) => HttpStatus::UnsupportedMediaType,
+            JsonError::MissingBody | JsonError::Invalid(_) => HttpStatus::BadRequest,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::MissingBody => write!(f, "request has no body"),
+            JsonError::UnsupportedContentType(Some(content_type)) => {
+                write!(f, "expected a json content type, got {content_type}")
+            }
+            JsonError::UnsupportedContentType(None) => write!(f, "expected a json content type"),
+            JsonError::Invalid(err) => write!(f, "invalid json body: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonError::Invalid(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Error returned when the query string does not match the requested type, e.g. because a
+/// required parameter is missing or a value cannot be parsed
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct QueryError(serde_urlencoded::de::Error);
+
+#[cfg(feature = "serde")]
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid query string: {}", self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Error for QueryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
@@ -0,0 +1,496 @@
+use http_base::http::http_structs::{HttpData, HttpResponse, HttpStatus, HttpStream};
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+use super::{
+    conditional::etag_for,
+    date::DateTime,
+    head::{DEFAULT_MAX_HEADERS, DEFAULT_MAX_LINE_LENGTH},
+    mime,
+    parse::{read_body, read_headers, read_line},
+    status::HttpStatusExt,
+};
+
+/// Convenience helpers for building HttpResponses
+pub trait HttpResponseExt: Sized {
+    /// Returns the value of the first response header matching name, ignoring case
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpResponse, HttpResponseExt};
+    ///
+    /// let mut resp = HttpResponse::default();
+    /// resp.set_header("Content-Type", "text/plain");
+    /// assert_eq!(resp.header("content-type"), Some("text/plain"));
+    /// ```
+    fn header(&self, name: &str) -> Option<&str>;
+
+    /// Returns the values of all response headers matching name, ignoring case, e.g. every
+    /// `Set-Cookie` of a response read by the client
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpResponse, HttpResponseExt};
+    ///
+    /// let mut resp = HttpResponse::default();
+    /// resp.extra_headers = Some(vec![
+    ///     (String::from("Set-Cookie"), String::from("a=1")),
+    ///     (String::from("Set-Cookie"), String::from("b=2")),
+    /// ]);
+    /// assert_eq!(resp.header_values("set-cookie"), vec!["a=1", "b=2"]);
+    /// ```
+    fn header_values(&self, name: &str) -> Vec<&str>;
+
+    /// Sets a response header, replacing any existing header of the same name
+    fn set_header(&mut self, name: &str, value: &str);
+
+    /// Marks the response as not cacheable by setting `Cache-Control: no-store`
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpResponse, HttpResponseExt};
+    ///
+    /// let resp = HttpResponse::default().no_cache();
+    /// assert_eq!(resp.header("Cache-Control"), Some("no-store"));
+    /// ```
+    fn no_cache(self) -> Self;
+
+    /// Sets a strong ETag computed from the current body, streamed bodies are left untouched.
+    /// Requests with a matching If-None-Match header are answered with 304 Not Modified
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpData, HttpResponse, HttpResponseExt};
+    ///
+    /// let mut resp = HttpResponse::default();
+    /// resp.data = Some(HttpData::Bytes(b"hello".to_vec()));
+    /// let resp = resp.with_etag();
+    /// assert!(resp.header("ETag").is_some());
+    /// ```
+    fn with_etag(self) -> Self;
+
+    /// Sets the Last-Modified header, e.g. from a file's modification time.
+    /// Requests with an If-Modified-Since header not older than it are answered with 304 Not Modified
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpResponse, HttpResponseExt};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let resp = HttpResponse::default().with_last_modified(UNIX_EPOCH + Duration::from_secs(784111777));
+    /// assert_eq!(resp.header("Last-Modified"), Some("Sun, 06 Nov 1994 08:49:37 GMT"));
+    /// ```
+    fn with_last_modified(self, time: SystemTime) -> Self;
+
+    /// Builds a response streaming a file from disk, with Content-Length and Last-Modified taken
+    /// from the file metadata and Content-Type guessed from the extension. Errors opening the file,
+    /// e.g. `io::ErrorKind::NotFound`, are returned so the handler can map them to a status
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpResponse, HttpResponseExt, HttpStatus};
+    ///
+    /// let resp = HttpResponse::from_file("Cargo.toml").unwrap();
+    /// assert_eq!(resp.header("Content-Type"), Some("application/octet-stream"));
+    /// assert!(resp.header("Content-Length").is_some());
+    ///
+    /// let resp = HttpResponse::from_file("missing.html")
+    ///     .unwrap_or_else(|_| HttpResponse::new(String::from("1.1"), HttpStatus::NotFound, None, None));
+    /// assert_eq!(resp.status, HttpStatus::NotFound);
+    /// ```
+    fn from_file(path: impl AsRef<Path>) -> io::Result<Self>;
+
+    /// Parses a response from a stream, the counterpart of `HttpRequest::from_stream`. Interim
+    /// responses like 100 Continue are skipped. The body is read per Transfer-Encoding or
+    /// Content-Length, or until the stream ends. Reads are buffered, so bytes following the
+    /// response may be consumed as well
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpData, HttpResponse, HttpResponseExt, HttpStatus};
+    ///
+    /// let mut raw: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 404 Not Found\r\nContent-Length: 4\r\n\r\ngone";
+    /// let resp = HttpResponse::from_stream(&mut raw).unwrap();
+    /// assert_eq!(resp.status, HttpStatus::NotFound);
+    /// assert_eq!(resp.header("Content-Length"), Some("4"));
+    /// match resp.data {
+    ///     Some(HttpData::Bytes(body)) => assert_eq!(body, b"gone"),
+    ///     _ => panic!("expected a body"),
+    /// }
+    /// ```
+    fn from_stream(stream: &mut impl Read) -> io::Result<Self>;
+
+    /// Builds a 200 response whose body is produced lazily from an iterator of byte chunks, e.g.
+    /// rows of a CSV export. The length is unknown, so the body is sent with chunked
+    /// transfer-encoding, HTTP/1.0 clients read until the connection closes
+    /// Example:
+    /// ```rust
+    /// use std::io::Read;
+    /// use http_serv::{HttpData, HttpResponse, HttpResponseExt, HttpStatus};
+    ///
+    /// let rows = (1..=3).map(|id| format!("{id},user{id}\n").into_bytes());
+    /// let resp = HttpResponse::from_chunks(rows);
+    /// assert_eq!(resp.status, HttpStatus::Ok);
+    /// assert_eq!(resp.header("Content-Length"), None);
+    /// let Some(HttpData::Stream(mut body)) = resp.data else { panic!("expected a stream") };
+    /// let mut csv = String::new();
+    /// body.0.read_to_string(&mut csv).unwrap();
+    /// assert_eq!(csv, "1,user1\n2,user2\n3,user3\n");
+    /// ```
+    fn from_chunks<I>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+        I::IntoIter: Send + 'static;
+
+    /// Builds a 204 No Content response, which is sent without body and without Content-Length
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpResponse, HttpResponseExt, HttpStatus};
+    ///
+    /// let resp = HttpResponse::no_content();
+    /// assert_eq!(resp.status, HttpStatus::NoContent);
+    /// assert!(resp.data.is_none());
+    /// assert_eq!(resp.header("Content-Length"), None);
+    /// ```
+    fn no_content() -> Self;
+
+    /// Builds a 201 Created response with the Location header pointing to the new resource
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpResponse, HttpResponseExt, HttpStatus};
+    ///
+    /// let resp = HttpResponse::created("/users/42", None);
+    /// assert_eq!(resp.status, HttpStatus::Created);
+    /// assert_eq!(resp.header("Location"), Some("/users/42"));
+    /// ```
+    fn created(location: &str, body: Option<HttpData>) -> Self;
+
+    /// Builds a redirect to location with the given 3xx status, which should be one of 300, 301,
+    /// 302, 303, 307 or 308. Passing another status is a bug in the caller and panics in debug
+    /// builds, release builds send the response with that status and the Location header as is
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpResponse, HttpResponseExt, HttpStatus};
+    ///
+    /// let resp = HttpResponse::redirect(HttpStatus::SeeOther, "/login");
+    /// assert_eq!(resp.status, HttpStatus::SeeOther);
+    /// assert_eq!(resp.header("Location"), Some("/login"));
+    /// ```
+    fn redirect(status: HttpStatus, location: &str) -> Self;
+
+    /// Builds a 307 Temporary Redirect to location, which keeps the request method and body
+    fn redirect_temporary(location: &str) -> Self {
+        Self::redirect(HttpStatus::TemporaryRedirect, location)
+    }
+
+    /// Builds a 308 Permanent Redirect to location, which keeps the request method and body
+    fn redirect_permanent(location: &str) -> Self {
+        Self::redirect(HttpStatus::PermanentRedirect, location)
+    }
+
+    /// Builds a response with value serialized as JSON body and a JSON Content-Type.
+    /// Values that cannot be serialized, e.g. maps with non string keys, result in a 500 response
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpData, HttpResponse, HttpResponseExt, HttpStatus};
+    ///
+    /// let resp = HttpResponse::json_body(HttpStatus::Ok, &vec![1, 2, 3]);
+    /// assert_eq!(resp.header("Content-Type"), Some("application/json"));
+    /// match resp.data {
+    ///     Some(HttpData::Bytes(body)) => assert_eq!(body, b"[1,2,3]"),
+    ///     _ => panic!("expected a json body"),
+    /// }
+    /// ```
+    #[cfg(feature = "json")]
+    fn json_body<T: Serialize>(status: HttpStatus, value: &T) -> Self;
+
+    /// Builds a 422 response with a JSON body mapping field names to their validation errors
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpData, HttpResponse, HttpResponseExt};
+    ///
+    /// let resp = HttpResponse::unprocessable(vec![(String::from("name"), String::from("must not be empty"))]);
+    /// assert_eq!(resp.header("Content-Type"), Some("application/json"));
+    /// match resp.data {
+    ///     Some(HttpData::Bytes(body)) => assert_eq!(body, br#"{"name":"must not be empty"}"#),
+    ///     _ => panic!("expected a json body"),
+    /// }
+    /// ```
+    #[cfg(feature = "json")]
+    fn unprocessable(errors: Vec<(String, String)>) -> Self;
+}
+
+impl HttpResponseExt for HttpResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.extra_headers
+            .as_ref()?
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn header_values(&self, name: &str) -> Vec<&str> {
+        self.extra_headers
+            .iter()
+            .flatten()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    fn set_header(&mut self, name: &str, value: &str) {
+        let headers = self.extra_headers.get_or_insert_with(Vec::new);
+        headers.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        headers.push((name.to_owned(), value.to_owned()));
+    }
+
+    fn no_cache(mut self) -> Self {
+        self.set_header("Cache-Control", "no-store");
+        self
+    }
+
+    fn with_etag(mut self) -> Self {
+        if let Some(HttpData::Bytes(bytes)) = &self.data {
+            let etag = etag_for(bytes);
+            self.set_header("ETag", &etag);
+        }
+        self
+    }
+
+    fn with_last_modified(mut self, time: SystemTime) -> Self {
+        let date = DateTime::from_system_time(time).to_http_date();
+        self.set_header("Last-Modified", &date);
+        self
+    }
+
+    fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        if metadata.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "path is a directory",
+            ));
+        }
+        let mut response = HttpResponse::new(
+            String::from("1.1"),
+            HttpStatus::Ok,
+            None,
+            Some(HttpData::Stream(HttpStream(Box::new(file)))),
+        );
+        response.set_header("Content-Type", mime::guess(path));
+        // streamed bodies get no Content-Length of their own
+        response.set_header("Content-Length", &metadata.len().to_string());
+        if let Ok(modified) = metadata.modified() {
+            response = response.with_last_modified(modified);
+        }
+        Ok(response)
+    }
+
+    fn from_stream(stream: &mut impl Read) -> io::Result<Self> {
+        read_response(&mut BufReader::new(stream), false, u64::MAX)
+    }
+
+    fn from_chunks<I>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+        I::IntoIter: Send + 'static,
+    {
+        let reader = ChunkIterReader::new(chunks.into_iter());
+        HttpResponse::new(
+            String::from("1.1"),
+            HttpStatus::Ok,
+            None,
+            Some(HttpData::Stream(HttpStream(Box::new(reader)))),
+        )
+    }
+
+    fn no_content() -> Self {
+        HttpResponse::new(String::from("1.1"), HttpStatus::NoContent, None, None)
+    }
+
+    fn created(location: &str, body: Option<HttpData>) -> Self {
+        let mut response = HttpResponse::new(String::from("1.1"), HttpStatus::Created, None, body);
+        response.set_header("Location", location);
+        response
+    }
+
+    fn redirect(status: HttpStatus, location: &str) -> Self {
+        debug_assert!(
+            matches!(status.code(), 300..=303 | 307 | 308),
+            "{status:?} is not a redirect status"
+        );
+        let mut response = HttpResponse::new(String::from("1.1"), status, None, None);
+        response.set_header("Location", location);
+        response
+    }
+
+    #[cfg(feature = "json")]
+    fn json_body<T: Serialize>(status: HttpStatus, value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => {
+                let mut response = HttpResponse::new(
+                    String::from("1.1"),
+                    status,
+                    None,
+                    Some(HttpData::Bytes(body)),
+                );
+                response.set_header("Content-Type", "application/json");
+                response
+            }
+            Err(_err) => {
+                #[cfg(feature = "log")]
+                log::error!("Failed to serialize json body: {_err}");
+                HttpResponse::new(
+                    String::from("1.1"),
+                    HttpStatus::InternalServerError,
+                    None,
+                    None,
+                )
+            }
+        }
+    }
+
+    #[cfg(feature = "json")]
+    fn unprocessable(errors: Vec<(String, String)>) -> Self {
+        let body = serde_json::Value::Object(
+            errors
+                .into_iter()
+                .map(|(field, message)| (field, serde_json::Value::String(message)))
+                .collect(),
+        );
+        Self::json_body(HttpStatus::UnprocessableContent, &body)
+    }
+}
+
+/// Adapts an iterator of byte chunks to Read, so it can be sent as a streamed body. The body
+/// ends once the iterator does, blocking iterators like a channel receiver block the read
+pub(crate) struct ChunkIterReader<I> {
+    chunks: I,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl<I> ChunkIterReader<I> {
+    pub(crate) fn new(chunks: I) -> Self {
+        ChunkIterReader {
+            chunks,
+            pending: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Vec<u8>>> Read for ChunkIterReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // empty chunks are skipped, returning 0 for them would end the body
+        while self.position >= self.pending.len() {
+            let Some(chunk) = self.chunks.next() else {
+                return Ok(0);
+            };
+            self.pending = chunk;
+            self.position = 0;
+        }
+        let length = buf.len().min(self.pending.len() - self.position);
+        buf[..length].copy_from_slice(&self.pending[self.position..self.position + length]);
+        self.position += length;
+        Ok(length)
+    }
+}
+
+/// Largest response body buffered from an upstream server, see `HttpClient` and `proxy::forward`
+pub(crate) const MAX_UPSTREAM_BODY: u64 = 10 * 1024 * 1024;
+/// How long a read from an upstream server may block before the response is given up
+pub(crate) const UPSTREAM_READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Most interim 1xx responses skipped before the final response
+const MAX_INTERIM_RESPONSES: usize = 10;
+
+/// Reads a response, its body framed by Transfer-Encoding, Content-Length or the end of the
+/// connection. Up to MAX_INTERIM_RESPONSES interim 1xx responses like 100 Continue are skipped,
+/// except for 101 Switching Protocols which ends the response. Responses to HEAD requests and
+/// 101, 204 and 304 responses have no body. Headers are kept as received, repeated headers like
+/// Set-Cookie included. They are bounded as request heads are by default, more or longer lines,
+/// more interim responses and bodies larger than max_size are an `io::ErrorKind::InvalidData`
+/// error
+pub(crate) fn read_response(
+    reader: &mut impl BufRead,
+    head_request: bool,
+    max_size: u64,
+) -> io::Result<HttpResponse> {
+    let mut interim = 0;
+    let (status, headers) = loop {
+        let mut line = String::new();
+        read_line(reader, &mut line, DEFAULT_MAX_LINE_LENGTH)?;
+        // HTTP/1.1 200 OK
+        let code: u16 = line
+            .split(' ')
+            .nth(1)
+            .and_then(|code| code.trim().parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid status line"))?;
+        let headers = read_headers(reader, DEFAULT_MAX_HEADERS, DEFAULT_MAX_LINE_LENGTH)?;
+        // interim responses are followed by the final one
+        if matches!(code, 100 | 102..=199) {
+            interim += 1;
+            if interim > MAX_INTERIM_RESPONSES {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "too many interim responses",
+                ));
+            }
+            continue;
+        }
+        let status = HttpStatus::from_u16(code)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown status code"))?;
+        break (status, headers);
+    };
+    let has_body = !head_request && !matches!(status.code(), 100..=199 | 204 | 304);
+    let body = match has_body {
+        true => match read_body(reader, &headers, max_size)? {
+            Some(body) => Some(body.data),
+            None => {
+                let mut body = Vec::new();
+                // one byte more than allowed tells an oversized body from one of exactly max_size
+                reader.by_ref().take(max_size.saturating_add(1)).read_to_end(&mut body)?;
+                if body.len() as u64 > max_size {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "response body too large",
+                    ));
+                }
+                Some(body)
+            }
+        },
+        false => None,
+    };
+
+    let mut response =
+        HttpResponse::new(String::from("1.1"), status, None, body.map(HttpData::Bytes));
+    response.extra_headers = Some(headers);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interim_responses_and_status_line_are_bounded() {
+        let response = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 204 No Content\r\n\r\n";
+        let response = read_response(&mut &response[..], false, 1024).unwrap();
+        assert_eq!(response.status.code(), 204);
+
+        let interim = "HTTP/1.1 102 Processing\r\n\r\n".repeat(MAX_INTERIM_RESPONSES + 1);
+        let Err(err) = read_response(&mut interim.as_bytes(), false, 1024) else {
+            panic!("more interim responses than allowed were skipped");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let long = format!("HTTP/1.1 200 {}\r\n\r\n", "O".repeat(DEFAULT_MAX_LINE_LENGTH));
+        let Err(err) = read_response(&mut long.as_bytes(), false, 1024) else {
+            panic!("a status line longer than allowed was accepted");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
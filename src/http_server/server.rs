@@ -1,23 +1,184 @@
+#[cfg(feature = "threading")]
+use std::sync::{
+    mpsc::{SyncSender, TrySendError},
+    Mutex,
+};
 use std::{
-    io::{self, Write},
-    net::{TcpListener, TcpStream},
+    io::{self, BufReader, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 
-use http_base::http::http_structs::{HttpMethod, HttpRequest, HttpResponse};
+use http_base::http::http_structs::{
+    HttpData, HttpHeaders, HttpMethod, HttpRequest, HttpResponse, HttpStatus,
+};
+use socket2::Socket;
 
+#[cfg(feature = "log")]
+use super::access_log::{AccessLogEntry, AccessLogFormat};
+use super::{
+    chunked::ChunkedWriter,
+    conditional::apply_conditional,
+    cors::Cors,
+    date::DateTime,
+    drain::Drain,
+    files,
+    head::{
+        read_head, HeadLimits, RequestHead, DEFAULT_MAX_HEADERS, DEFAULT_MAX_LINE_LENGTH,
+    },
+    method::HttpMethodExt,
+    metrics::{Counters, Metrics},
+    parse::{
+        body_reader, content_length, declared_trailers, discard_input, is_chunked,
+        read_request_body, BodyLimits, RequestBody,
+    },
+    range::apply_range,
+    rate_limit::{AcceptThrottle, RateLimiter},
+    request::{
+        split_host_port, with_connection, ConnectionInfo, HttpRequestExt,
+    },
+    response::HttpResponseExt,
+    status::HttpStatusExt,
+    websocket,
+};
 #[cfg(feature = "log")]
 extern crate pretty_env_logger;
 
 type HttpHandlerFn = Box<dyn (Fn(&HttpRequest) -> HttpResponse) + Sync + Send + 'static>;
+// Request Head, handlers registered with capture_raw also get the head as received
+type RouteFn = Box<dyn (Fn(&HttpRequest, &[u8]) -> HttpResponse) + Sync + Send + 'static>;
+// Method Path Closure, shared so handlers can run on a separate thread when timed out
+type HttpHandler = (HttpMethod, String, Arc<RouteFn>);
+type StreamHandlerFn = Box<dyn Fn(&HttpRequest, TcpStream) + Sync + Send + 'static>;
+// Method Path Closure Websocket
+type StreamHandler = (HttpMethod, String, StreamHandlerFn, bool);
+// Target Headers Stream, CONNECT is not an HttpMethod so there is no HttpRequest
+type ConnectHandlerFn =
+    Box<dyn Fn(&str, &[(String, String)], TcpStream) + Sync + Send + 'static>;
+type BodyHandlerFn =
+    Box<dyn Fn(&HttpRequest, &mut dyn Read) -> HttpResponse + Sync + Send + 'static>;
+// Method Path Closure, the closure reads the body itself
+type BodyHandler = (HttpMethod, String, BodyHandlerFn);
+type ExpectHandlerFn = Box<dyn Fn(&HttpHeaders) -> Option<HttpResponse> + Sync + Send + 'static>;
+type ErrorHandlerFn = Box<dyn Fn(HttpResponse) -> HttpResponse + Sync + Send + 'static>;
+type RawResponseFn = Box<dyn Fn(&[u8]) -> Vec<u8> + Sync + Send + 'static>;
+
+/// How much of an upload body left unread by its handler is discarded before responding
+const MAX_UNREAD_BODY: u64 = 1024 * 1024;
+/// How long the request of a rejected connection or head is discarded, rejections block the
+/// accept loop or worker sending them
+const REJECT_DISCARD_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How routes with and without a trailing slash are matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingSlash {
+    /// `/users` and `/users/` are different routes
+    #[default]
+    Strict,
+    /// `/users` and `/users/` are treated as the same route
+    Lenient,
+    /// Requests for the form that has no handler are redirected with 301 to the form that has one
+    Redirect,
+}
+
+/// Kind of handler a route was registered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteKind {
+    /// Registered with `get`, `post`, `route` and the like, or through `host`
+    Handler,
+    /// Registered with `upload`
+    Upload,
+    /// Registered with `websocket`
+    Websocket,
+    /// Registered with `hijack`
+    Hijack,
+}
+
+impl RouteKind {
+    /// Requests are matched against upload routes first, then against websocket and hijacking
+    /// routes and last against the regular handlers
+    fn precedence(self) -> u8 {
+        match self {
+            RouteKind::Upload => 0,
+            RouteKind::Websocket | RouteKind::Hijack => 1,
+            RouteKind::Handler => 2,
+        }
+    }
+}
+
+/// A route that never receives a request, as another route for the same method and path is
+/// matched first, see [`HttpServer::shadowed_routes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowedRoute<'a> {
+    pub method: HttpMethod,
+    pub path: &'a str,
+    /// Host of a route registered through `host`
+    pub host: Option<&'a str>,
+    pub kind: RouteKind,
+    /// Kind of the route matched instead
+    pub shadowed_by: RouteKind,
+}
 
 /// Represents the http server
 pub struct HttpServer {
-    listener: TcpListener,
+    // the first listener is the one bound by new
+    listeners: Vec<TcpListener>,
     #[cfg(feature = "threading")]
     threads: u32,
+    #[cfg(feature = "threading")]
+    queue_size: usize,
+    #[cfg(feature = "threading")]
+    max_connections: Option<usize>,
+    // connections handed to the workers which are not yet finished
+    #[cfg(feature = "threading")]
+    pending: AtomicUsize,
     // Method Path Closure
-    handlers: Vec<(HttpMethod, String, HttpHandlerFn)>,
-    default_handler: HttpHandlerFn,
+    handlers: Vec<HttpHandler>,
+    default_handler: Arc<RouteFn>,
+    no_cache: bool,
+    trailing_slash: TrailingSlash,
+    case_insensitive_routes: bool,
+    max_accept_rate: Option<u32>,
+    #[cfg(feature = "log")]
+    access_log: Option<AccessLogFormat>,
+    cors: Option<Cors>,
+    auto_head: bool,
+    trust_proxy: bool,
+    rate_limiter: Option<RateLimiter>,
+    etag: bool,
+    stream_handlers: Vec<StreamHandler>,
+    connect_handler: Option<ConnectHandlerFn>,
+    body_handlers: Vec<BodyHandler>,
+    expect_continue: Option<ExpectHandlerFn>,
+    head_limits: HeadLimits,
+    body_limits: BodyLimits,
+    chunked_trailers: bool,
+    max_query_params: usize,
+    server_header: Option<String>,
+    date_header: bool,
+    // Status Closure
+    error_handlers: Vec<(u16, ErrorHandlerFn)>,
+    raw_response: Option<RawResponseFn>,
+    handler_timeout: Option<Duration>,
+    max_handler_threads: usize,
+    // handler threads spawned for handler_timeout which have not returned yet
+    handler_threads: Arc<AtomicUsize>,
+    tcp_nodelay: bool,
+    listen_backlog: Option<u32>,
+    // Host Handler
+    host_handlers: Vec<(String, HttpHandler)>,
+    // shared with the metrics route handler
+    counters: Arc<Counters>,
+    drain: Arc<Drain>,
+    shutdown_timeout: Option<Duration>,
 }
 
 impl HttpServer {
@@ -35,27 +196,12 @@ impl HttpServer {
         handlers: Vec<(HttpMethod, String, HttpHandlerFn)>,
         default_handler: Option<HttpHandlerFn>,
     ) -> Result<Self, std::io::Error> {
-        let default_handler_defined = match default_handler {
-            Some(default_handler_defined) => default_handler_defined,
-            None => Box::new(|_: &_| {
-                HttpResponse::new(
-                    String::from("1.1"),
-                    http_base::http::http_structs::HttpStatus::NotImplemented,
-                    None,
-                    None,
-                )
-            }),
-        };
-        Ok(Self {
-            listener: TcpListener::bind(format!("{addr}:{port}"))?,
-            handlers,
-            default_handler: default_handler_defined,
-        })
+        Self::create(addr, port, handlers, default_handler)
     }
     /// Creates new instance of HttpServer
     /// Examples:
     /// ```rust
-    /// use http_serv::http::server::HttpServer;
+    /// use http_serv::http_server::server::HttpServer;
     ///
     /// // If num_cpus is enabled, threads can be set as the third arg. If no number is supplied, num_cpus will assume corecount * 3
     /// #[cfg(feature = "num_cpus")]
@@ -79,6 +225,32 @@ impl HttpServer {
             Some(threads) => threads,
             None => (num_cpus::get() as u32) * 3,
         };
+        Self::create(addr, port, threads, handlers, default_handler)
+    }
+
+    /// Creates a server from the arguments `new` takes without threading, with two worker threads
+    /// if it is enabled, so the examples compile with every feature set
+    #[doc(hidden)]
+    pub fn example(
+        addr: String,
+        port: String,
+        handlers: Vec<(HttpMethod, String, HttpHandlerFn)>,
+        default_handler: Option<HttpHandlerFn>,
+    ) -> Result<Self, std::io::Error> {
+        #[cfg(feature = "threading")]
+        return Self::create(addr, port, 2, handlers, default_handler);
+        #[cfg(not(feature = "threading"))]
+        Self::create(addr, port, handlers, default_handler)
+    }
+
+    /// Binds the listener and sets every option to its default, for both variants of `new`
+    fn create(
+        addr: String,
+        port: String,
+        #[cfg(feature = "threading")] threads: u32,
+        handlers: Vec<(HttpMethod, String, HttpHandlerFn)>,
+        default_handler: Option<HttpHandlerFn>,
+    ) -> Result<Self, std::io::Error> {
         let default_handler_defined = match default_handler {
             Some(default_handler_defined) => default_handler_defined,
             None => Box::new(|_: &_| {
@@ -91,74 +263,167 @@ impl HttpServer {
             }),
         };
         Ok(Self {
-            listener: TcpListener::bind(format!("{addr}:{port}"))?,
+            listeners: vec![TcpListener::bind(format!("{addr}:{port}"))?],
+            #[cfg(feature = "threading")]
             threads,
-            handlers,
-            default_handler: default_handler_defined,
+            #[cfg(feature = "threading")]
+            queue_size: threads as usize,
+            #[cfg(feature = "threading")]
+            max_connections: None,
+            #[cfg(feature = "threading")]
+            pending: AtomicUsize::new(0),
+            handlers: handlers
+                .into_iter()
+                .map(|(method, path, exec)| (method, path, Arc::new(ignore_head(exec))))
+                .collect(),
+            default_handler: Arc::new(ignore_head(default_handler_defined)),
+            no_cache: false,
+            trailing_slash: TrailingSlash::Strict,
+            case_insensitive_routes: false,
+            max_accept_rate: None,
+            #[cfg(feature = "log")]
+            access_log: Some(AccessLogFormat::Common),
+            cors: None,
+            auto_head: false,
+            trust_proxy: false,
+            rate_limiter: None,
+            etag: false,
+            stream_handlers: Vec::new(),
+            connect_handler: None,
+            body_handlers: Vec::new(),
+            expect_continue: None,
+            head_limits: HeadLimits {
+                max_headers: DEFAULT_MAX_HEADERS,
+                max_line_length: DEFAULT_MAX_LINE_LENGTH,
+                timeout: Duration::from_secs(10),
+            },
+            body_limits: BodyLimits {
+                max_size: 10 * 1024 * 1024,
+                timeout: Duration::from_secs(60),
+            },
+            chunked_trailers: false,
+            max_query_params: 1000,
+            server_header: None,
+            date_header: true,
+            error_handlers: Vec::new(),
+            raw_response: None,
+            handler_timeout: None,
+            max_handler_threads: 256,
+            handler_threads: Arc::new(AtomicUsize::new(0)),
+            tcp_nodelay: true,
+            listen_backlog: None,
+            host_handlers: Vec::new(),
+            counters: Arc::new(Counters::default()),
+            drain: Arc::new(Drain::default()),
+            shutdown_timeout: None,
         })
     }
 
-    /// Main server loop that handles incoming connections
+    /// Main server loop that handles incoming connections.
+    /// The server is only borrowed, the workers are scoped threads sharing it for the lifetime of
+    /// the loop. Configuration is done through `&mut self` before the loop starts, state that
+    /// changes while serving is kept in atomics so it can be updated from every worker
     /// ```ignore
-    /// use http_serv::http::server::HttpServer;
+    /// use http_serv::{HttpRequest, HttpResponse, http_server::server::HttpServer};
     ///
-    /// let server = HttpServer::new(String::from("127.0.0.1"), String::from("8443"), Some(10), Vec::new()).unwrap();
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new(String::from("127.0.0.1"), String::from("8443"), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example(String::from("127.0.0.1"), String::from("8443"), Vec::new(), None).unwrap();
+    /// // registration methods return the server, so they can be chained
+    /// server
+    ///     .get("/".to_owned(), |_: &HttpRequest| HttpResponse::default())
+    ///     .post("/users".to_owned(), |_: &HttpRequest| HttpResponse::default());
     /// server.run_loop().unwrap();
     /// ```
     pub fn run_loop(&self) -> std::io::Result<()> {
+        #[cfg(feature = "log")]
+        for route in self.shadowed_routes() {
+            log::warn!(
+                "{:?} {} ({:?} route{}) is never used, the {:?} route for the same path is matched first",
+                route.method,
+                route.path,
+                route.kind,
+                route.host.map_or_else(String::new, |host| format!(" for host {host}")),
+                route.shadowed_by
+            );
+        }
+        if let Some(backlog) = self.listen_backlog {
+            for listener in &self.listeners {
+                // listening again on the bound socket only changes its backlog
+                Socket::from(listener.try_clone()?).listen(i32::try_from(backlog).unwrap_or(i32::MAX))?;
+            }
+        }
         #[cfg(feature = "threading")]
         {
-            let mut threadpool = scoped_threadpool::Pool::new(self.threads);
-            threadpool.scoped(|scope| {
-                // accepts connection
-                for stream in self.listener.incoming() {
-                    let mut stream = match stream {
-                        Ok(stream) => stream,
-                        Err(_err) => {
-                            #[cfg(feature = "log")]
-                            log::error!("Failed to get stream: {_err}");
-                            continue;
-                        }
-                    };
-                    let http_request = match HttpRequest::from_stream(&mut stream) {
-                        Ok(http_request) => http_request,
-                        Err(_err) => {
-                            #[cfg(feature = "log")]
-                            log::error!("Failed to build http_request: {_err}");
-                            continue;
-                        }
-                    };
-                    #[cfg(feature = "log")]
-                    log::info!(
-                        "[{}]: {:?} {}",
-                        stream.peer_addr().unwrap_or(std::net::SocketAddr::new(
-                            std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)),
-                            0000
-                        )),
-                        http_request.http_headers.method,
-                        http_request.http_headers.path
-                    );
-                    scope.execute(|| {
-                        match handle_connection(
-                            stream,
-                            http_request,
-                            &self.handlers,
-                            &self.default_handler,
-                        ) {
-                            Ok(_) => {}
-                            Err(_err) => {
-                                #[cfg(feature = "log")]
-                                log::error!("Encountered error handling connection: {_err}");
-                            }
+            // accepted connections wait here until a worker is free
+            let (sender, receiver) = mpsc::sync_channel::<TcpStream>(self.queue_size);
+            let receiver = &Mutex::new(receiver);
+            // the sender is moved in, so the workers stop once every accept loop returned
+            thread::scope(move |scope| -> std::io::Result<()> {
+                for idx in 0..self.threads {
+                    let worker = thread::Builder::new().name(format!("http-worker-{idx}"));
+                    worker.spawn_scoped(scope, move || loop {
+                        // the lock is only held while waiting for the next connection
+                        let stream = match receiver.lock() {
+                            Ok(receiver) => receiver.recv(),
+                            Err(_) => break,
                         };
-                    });
+                        match stream {
+                            // the request is parsed on the worker so slow clients do not block accepting
+                            Ok(stream) => self.handle_stream(stream),
+                            Err(_) => break,
+                        }
+                        self.pending.fetch_sub(1, Ordering::SeqCst);
+                    })?;
                 }
-            });
+                // additional listeners accept on their own threads and feed the same workers
+                for (idx, listener) in self.listeners.iter().enumerate().skip(1) {
+                    let sender = sender.clone();
+                    thread::Builder::new()
+                        .name(format!("http-accept-{idx}"))
+                        .spawn_scoped(scope, move || {
+                            self.accept_loop(listener, |stream| self.queue_stream(stream, &sender))
+                        })?;
+                }
+                self.accept_loop(&self.listeners[0], |stream| self.queue_stream(stream, &sender));
+                Ok(())
+            })?;
         }
 
         #[cfg(not(feature = "threading"))]
-        for stream in self.listener.incoming() {
-            let mut stream = match stream {
+        {
+            thread::scope(|scope| -> std::io::Result<()> {
+                // without workers every listener serves its connections one at a time
+                for (idx, listener) in self.listeners.iter().enumerate().skip(1) {
+                    thread::Builder::new()
+                        .name(format!("http-accept-{idx}"))
+                        .spawn_scoped(scope, move || {
+                            self.accept_loop(listener, |stream| {
+                                self.handle_stream(stream);
+                                true
+                            })
+                        })?;
+                }
+                self.accept_loop(&self.listeners[0], |stream| {
+                    self.handle_stream(stream);
+                    true
+                });
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Accepts connections on the listener until shutdown is requested or serve returns false
+    fn accept_loop(&self, listener: &TcpListener, mut serve: impl FnMut(TcpStream) -> bool) {
+        let mut throttle = self.max_accept_rate.map(AcceptThrottle::new);
+        for stream in listener.incoming() {
+            if self.drain.is_requested() {
+                break;
+            }
+            let stream = match stream {
                 Ok(stream) => stream,
                 Err(_err) => {
                     #[cfg(feature = "log")]
@@ -166,34 +431,103 @@ impl HttpServer {
                     continue;
                 }
             };
-            let http_request = match HttpRequest::from_stream(&mut stream) {
-                Ok(http_request) => http_request,
-                Err(_err) => {
-                    #[cfg(feature = "log")]
-                    log::error!("Failed to build http_request: {_err}");
-                    continue;
-                }
-            };
+            // connections arriving while sleeping wait in the OS backlog
+            if let Some(throttle) = &mut throttle {
+                thread::sleep(throttle.delay(Instant::now()));
+            }
+            if !serve(stream) {
+                break;
+            }
+        }
+    }
+
+    /// Hands an accepted connection to the workers, returns false once they are gone
+    #[cfg(feature = "threading")]
+    fn queue_stream(&self, stream: TcpStream, sender: &SyncSender<TcpStream>) -> bool {
+        if self
+            .max_connections
+            .is_some_and(|max_connections| self.pending.load(Ordering::SeqCst) >= max_connections)
+        {
             #[cfg(feature = "log")]
-            log::info!(
-                "[{}]: {:?} {}",
-                stream.peer_addr().unwrap_or(std::net::SocketAddr::new(
-                    std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)),
-                    0000
-                )),
-                http_request.http_headers.method,
-                http_request.http_headers.path
-            );
-            match handle_connection(stream, http_request, &self.handlers, &self.default_handler) {
-                Ok(_) => {}
-                Err(_err) => {
-                    #[cfg(feature = "log")]
-                    log::error!("Encountered error handling connection: {_err}");
-                }
-            };
+            log::warn!("Connection limit reached, rejecting connection");
+            self.reject(stream);
+            return true;
+        }
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        match sender.try_send(stream) {
+            Ok(()) => true,
+            // all workers are busy and the queue is full, reject instead of stalling
+            Err(TrySendError::Full(stream)) => {
+                self.pending.fetch_sub(1, Ordering::SeqCst);
+                #[cfg(feature = "log")]
+                log::warn!("Worker queue is full, rejecting connection");
+                self.reject(stream);
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
         }
+    }
 
-        Ok(())
+    /// Sets how many accepted connections may wait for a free worker, defaults to the thread count.
+    /// Once all workers are busy and the queue is full, new connections are answered with 503
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// #[cfg(feature = "num_cpus")]
+    /// let mut server = HttpServer::new(String::from("127.0.0.1"), String::from("8443"), Some(2), Vec::new(), None).unwrap();
+    /// #[cfg(not(feature = "num_cpus"))]
+    /// let mut server = HttpServer::new(String::from("127.0.0.1"), String::from("8443"), 2, Vec::new(), None).unwrap();
+    /// // at most 2 connections are handled and 8 are queued at once
+    /// server.queue_size(8);
+    /// ```
+    #[cfg(feature = "threading")]
+    pub fn queue_size(&mut self, size: usize) -> &mut Self {
+        self.queue_size = size;
+        self
+    }
+
+    /// Limits how many connections are in flight (handled or queued) at once.
+    /// Connections beyond the limit are answered with 503 Service Unavailable, independent of queue_size
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// #[cfg(feature = "num_cpus")]
+    /// let mut server = HttpServer::new(String::from("127.0.0.1"), String::from("8443"), Some(2), Vec::new(), None).unwrap();
+    /// #[cfg(not(feature = "num_cpus"))]
+    /// let mut server = HttpServer::new(String::from("127.0.0.1"), String::from("8443"), 2, Vec::new(), None).unwrap();
+    /// // while one connection is handled or queued, further ones are answered with 503
+    /// server.max_connections(1);
+    /// ```
+    #[cfg(feature = "threading")]
+    pub fn max_connections(&mut self, max: usize) -> &mut Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Answers a connection with 503 Service Unavailable without parsing its request. The write
+    /// side is shut down with the response, what the client sends is discarded for a moment so
+    /// closing the connection does not reset it before the client has read the response
+    #[cfg(feature = "threading")]
+    fn reject(&self, mut stream: TcpStream) {
+        let response = self.rejection(HttpResponse::new(
+            String::from("1.1"),
+            HttpStatus::ServiceUnavailable,
+            None,
+            None,
+        ));
+        match self.write_rejection(&mut stream, response) {
+            Ok(()) => {}
+            Err(_err) if is_disconnect(&_err) => {
+                #[cfg(feature = "log")]
+                log::debug!("Client disconnected before being rejected: {_err}");
+            }
+            Err(_err) => {
+                #[cfg(feature = "log")]
+                log::error!("Failed to reject connection: {_err}");
+            }
+        }
     }
 
     /// Adds a get method handler to the server
@@ -201,27 +535,101 @@ impl HttpServer {
     /// ```rust
     /// use http_serv::{HttpData, HttpResponse, HttpRequest, http_server::server::HttpServer};
     /// 
+    /// # #[cfg(not(feature = "threading"))]
     /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
-    /// // :tag in a path will be used as route parameter
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// // :tag in a path will be used as route parameter, stored under the name "tag"
     /// server.get("/:uri".to_owned(), Box::new(|request: &HttpRequest| {
     ///     let mut resp = HttpResponse::default();
     ///     resp.data = Some(HttpData::Bytes(format!("{:#?}", request).as_bytes().to_vec()));
     ///     return resp;
     /// }));
+    /// // plain functions work as handlers too
+    /// fn health(_: &HttpRequest) -> HttpResponse {
+    ///     HttpResponse::default()
+    /// }
+    /// server.get("/health".to_owned(), health);
+    /// // a trailing * matches the rest of the path and is stored as the route param "*".
+    /// // Wildcard routes are only used when no other route matches, so this serves every unmatched GET
+    /// server.get("/*".to_owned(), Box::new(|request: &HttpRequest| {
+    ///     let mut resp = HttpResponse::default();
+    ///     resp.data = Some(HttpData::Bytes(format!("{:?}", request.route_params).as_bytes().to_vec()));
+    ///     return resp;
+    /// }));
+    /// ```
+    /// Named wildcards like `:path*` are stored under their name and hold the rest of the path without the leading slash, or an
+    /// empty string if nothing follows. Requests shorter than the sections before the wildcard do
+    /// not match:
+    /// ```rust
+    /// use http_serv::{HttpData, HttpResponse, HttpRequest, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// // /files/a/b.txt stores "a/b.txt" under "path", /files and /files/ store an empty string
+    /// server.get("/files/:path*".to_owned(), |request: &HttpRequest| {
+    ///     let path = request
+    ///         .route_params
+    ///         .iter()
+    ///         .flatten()
+    ///         .find(|(key, _)| key == "path")
+    ///         .map(|(_, value)| value.clone());
+    ///     let mut resp = HttpResponse::default();
+    ///     resp.data = Some(HttpData::Bytes(format!("{path:?}").into_bytes()));
+    ///     resp
+    /// });
     /// ```
-    pub fn get(&mut self, path: String, exec: HttpHandlerFn) -> &mut Self {
+    pub fn get(
+        &mut self,
+        path: String,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
         #[cfg(feature = "log")]
         log::debug!("Adding GET {path}");
-        self.handlers.push((HttpMethod::GET, path, Box::new(exec)));
+        self.handlers
+            .push((HttpMethod::GET, path, Arc::new(ignore_head(exec))));
         self
     }
 
+    /// Adds a get method handler to the server whose responses default to `Content-Type: application/json`.
+    /// A Content-Type set by the handler is kept
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpData, HttpResponse, HttpResponseExt, HttpRequest, HttpStatus, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.get_json("/users".to_owned(), |_: &HttpRequest| {
+    ///     HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, Some(HttpData::Bytes(b"[]".to_vec())))
+    /// });
+    /// // a Content-Type set by the handler wins over the default
+    /// server.get_json("/users.csv".to_owned(), |_: &HttpRequest| {
+    ///     let mut resp = HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, Some(HttpData::Bytes(b"id\n".to_vec())));
+    ///     resp.set_header("Content-Type", "text/csv");
+    ///     resp
+    /// });
+    /// ```
+    pub fn get_json(
+        &mut self,
+        path: String,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.get(path, with_default_content_type(exec, "application/json"))
+    }
+
     /// Adds a put method handler to the server
     /// Example:
     /// ```rust
     /// use http_serv::{HttpData, HttpResponse, HttpRequest, http_server::server::HttpServer};
     /// 
+    /// # #[cfg(not(feature = "threading"))]
     /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
     /// // :tag in a path will be used as route parameter
     /// server.put("/:uri".to_owned(), Box::new(|request: &HttpRequest| {
     ///     let mut resp = HttpResponse::default();
@@ -229,19 +637,74 @@ impl HttpServer {
     ///     return resp;
     /// }));
     /// ```
-    pub fn post(&mut self, path: String, exec: HttpHandlerFn) -> &mut Self {
+    pub fn post(
+        &mut self,
+        path: String,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
         #[cfg(feature = "log")]
         log::debug!("Adding POST {path}");
-        self.handlers.push((HttpMethod::POST, path, Box::new(exec)));
+        self.handlers
+            .push((HttpMethod::POST, path, Arc::new(ignore_head(exec))));
         self
     }
 
+    /// Adds a post method handler to the server which only accepts the given request content type.
+    /// Requests with a missing or different Content-Type are answered with 415 before the handler runs
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpResponse, HttpRequest, HttpStatus, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// // only requests with "Content-Type: application/json" will reach the handler
+    /// server.post_typed("/users".to_owned(), "application/json", |request: &HttpRequest| {
+    ///     // the handler still validates the body itself
+    ///     let body = request.data.as_deref().unwrap_or_default();
+    ///     let status = match body.starts_with(b"{") && body.ends_with(b"}") {
+    ///         true => HttpStatus::Created,
+    ///         false => HttpStatus::BadRequest,
+    ///     };
+    ///     HttpResponse::new(String::from("1.1"), status, None, None)
+    /// });
+    /// ```
+    pub fn post_typed(
+        &mut self,
+        path: String,
+        content_type: &str,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        let content_type = content_type.to_owned();
+        self.post(path, move |request: &HttpRequest| {
+            // compare the media type only, parameters like charset are ignored
+            let matches = request
+                .content_type()
+                .is_some_and(|media_type| media_type.eq_ignore_ascii_case(&content_type));
+            if !matches {
+                #[cfg(feature = "log")]
+                log::debug!("Rejecting request with unexpected content type, expected {content_type}");
+                return HttpResponse::new(
+                    String::from("1.1"),
+                    HttpStatus::UnsupportedMediaType,
+                    None,
+                    None,
+                );
+            }
+            exec(request)
+        })
+    }
+
     /// Adds a post method handler to the server
     /// Example:
     /// ```rust
     /// use http_serv::{HttpData, HttpResponse, HttpRequest, http_server::server::HttpServer};
     /// 
+    /// # #[cfg(not(feature = "threading"))]
     /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
     /// // :tag in a path will be used as route parameter
     /// server.post("/:uri".to_owned(), Box::new(|request: &HttpRequest| {
     ///     let mut resp = HttpResponse::default();
@@ -249,10 +712,15 @@ impl HttpServer {
     ///     return resp;
     /// }));
     /// ```
-    pub fn put(&mut self, path: String, exec: HttpHandlerFn) -> &mut Self {
+    pub fn put(
+        &mut self,
+        path: String,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
         #[cfg(feature = "log")]
         log::debug!("Adding PUT {path}");
-        self.handlers.push((HttpMethod::PUT, path, Box::new(exec)));
+        self.handlers
+            .push((HttpMethod::PUT, path, Arc::new(ignore_head(exec))));
         self
     }
 
@@ -261,7 +729,10 @@ impl HttpServer {
     /// ```rust
     /// use http_serv::{HttpData, HttpResponse, HttpRequest, http_server::server::HttpServer};
     /// 
+    /// # #[cfg(not(feature = "threading"))]
     /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
     /// // :tag in a path will be used as route parameter
     /// server.delete("/:uri".to_owned(), Box::new(|request: &HttpRequest| {
     ///     let mut resp = HttpResponse::default();
@@ -269,20 +740,173 @@ impl HttpServer {
     ///     return resp;
     /// }));
     /// ```
-    pub fn delete(&mut self, path: String, exec: HttpHandlerFn) -> &mut Self {
+    pub fn delete(
+        &mut self,
+        path: String,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
         #[cfg(feature = "log")]
         log::debug!("Adding DELETE {path}");
         self.handlers
-            .push((HttpMethod::DELETE, path, Box::new(exec)));
+            .push((HttpMethod::DELETE, path, Arc::new(ignore_head(exec))));
+        self
+    }
+
+    /// Adds a handler for several methods at once, e.g. GET and HEAD or PUT and PATCH. The handler
+    /// is shared between the methods instead of being cloned
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequest, HttpResponse, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.route(&[HttpMethod::GET, HttpMethod::HEAD], "/users".to_owned(), |_: &HttpRequest| {
+    ///     HttpResponse::default()
+    /// });
+    /// ```
+    pub fn route(
+        &mut self,
+        methods: &[HttpMethod],
+        path: String,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.add_route(methods, path, ignore_head(exec))
+    }
+
+    /// Adds a handler for several methods like `route`, which also gets the request line and
+    /// headers exactly as received, including the blank line ending them, e.g. to log or forward a
+    /// request faithfully. The body is not included. The head is bounded by the `max_headers` and
+    /// `max_header_line` limits
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, HttpRequest, HttpResponse, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.capture_raw(&[HttpMethod::GET], "/debug".to_owned(), |_: &HttpRequest, head: &[u8]| {
+    ///     eprint!("{}", String::from_utf8_lossy(head));
+    ///     HttpResponse::default()
+    /// });
+    /// ```
+    pub fn capture_raw(
+        &mut self,
+        methods: &[HttpMethod],
+        path: String,
+        exec: impl Fn(&HttpRequest, &[u8]) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.add_route(methods, path, Box::new(exec))
+    }
+
+    fn add_route(&mut self, methods: &[HttpMethod], path: String, exec: RouteFn) -> &mut Self {
+        let exec = Arc::new(exec);
+        for method in methods {
+            #[cfg(feature = "log")]
+            log::debug!("Adding {method:?} {path}");
+            self.handlers
+                .push((method.clone(), path.clone(), Arc::clone(&exec)));
+        }
         self
     }
 
+    /// Serves the files in dir for GET requests below prefix, with `index.html` for directory
+    /// requests, see `serve_dir_with_index`
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// // /static/app.js serves public/app.js
+    /// server.serve_dir("/static", "public");
+    /// ```
+    pub fn serve_dir(&mut self, prefix: &str, dir: impl Into<PathBuf>) -> &mut Self {
+        self.serve_dir_with_index(prefix, dir, "index.html")
+    }
+
+    /// Serves the files in dir for GET requests below prefix, streamed with Content-Type,
+    /// Content-Length and Last-Modified, single byte ranges are supported. Requests ending in a slash name a directory and serve
+    /// its index file, directories requested without the slash are redirected to it with 301.
+    /// Directories are not listed, see `serve_dir_with_listing`. Requests for missing files,
+    /// directories without index file or paths with `..` segments are answered with 404.
+    /// Symlinks in dir are followed
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// // /docs/guide/ serves public/docs/guide/start.html
+    /// server.serve_dir_with_index("/docs", "public/docs", "start.html");
+    /// ```
+    pub fn serve_dir_with_index(
+        &mut self,
+        prefix: &str,
+        dir: impl Into<PathBuf>,
+        index: &str,
+    ) -> &mut Self {
+        self.add_dir_route(prefix, dir.into(), index, false)
+    }
+
+    /// Serves the files in dir like `serve_dir_with_index`, but directories without index file are
+    /// answered with an HTML page listing their entries and sizes. Listings reveal every file in
+    /// the directory tree, only use this for directories meant to be browsed
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// // /share/ lists the entries of shared unless it has an index.html
+    /// server.serve_dir_with_listing("/share", "shared", "index.html");
+    /// ```
+    pub fn serve_dir_with_listing(
+        &mut self,
+        prefix: &str,
+        dir: impl Into<PathBuf>,
+        index: &str,
+    ) -> &mut Self {
+        self.add_dir_route(prefix, dir.into(), index, true)
+    }
+
+    fn add_dir_route(
+        &mut self,
+        prefix: &str,
+        dir: PathBuf,
+        index: &str,
+        listing: bool,
+    ) -> &mut Self {
+        let index = index.to_owned();
+        let route = format!("{}/*", prefix.trim_end_matches('/'));
+        self.get(route, move |request: &HttpRequest| {
+            let rest = request
+                .route_params
+                .iter()
+                .flatten()
+                .find(|(key, _)| key == "*")
+                .map_or("", |(_, rest)| rest.as_str());
+            files::serve(&dir, &index, listing, request, rest)
+        })
+    }
+
     /// Adds a default handler to the server
     /// Example:
     /// ```rust
     /// use http_serv::{HttpData, HttpResponse, HttpRequest, http_server::server::HttpServer};
     /// 
+    /// # #[cfg(not(feature = "threading"))]
     /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
     ///
     /// server.default(Box::new(|request: &HttpRequest| {
     ///     let mut resp = HttpResponse::default();
@@ -290,118 +914,2025 @@ impl HttpServer {
     ///     return resp;
     /// }));
     /// ```
-    pub fn default(&mut self, exec: HttpHandlerFn) -> &mut Self {
-        self.default_handler = exec;
+    pub fn default(
+        &mut self,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.default_handler = Arc::new(ignore_head(exec));
         self
     }
-}
 
-fn handle_connection(
-    mut stream: TcpStream,
-    mut http_request: HttpRequest,
-    handlers: &Vec<(HttpMethod, String, HttpHandlerFn)>,
-    default_handler: &HttpHandlerFn,
-) -> std::io::Result<()> {
-    //let mut http_request = http_request.clone();
-    let mut found_handler = false;
-    // checks which function to run
-    for handler in handlers {
-        let mut route_params = Vec::new();
-        let split_path = http_request.http_headers.path.split_once('?');
-
-        // split route and query params and parse query
-        let (route_params_unparsed, query_params): (String, Option<Vec<(String, String)>>) =
-            match split_path {
-                Some((rpu, qpu)) => {
-                    // parse query params
-                    (
-                        rpu.to_owned(),
-                        Some(http_request.query_params_from_string(qpu.to_owned())),
-                    )
-                }
-                None => (http_request.http_headers.path.clone(), None),
-            };
-
-        http_request.query_params = query_params;
-
-        // splits paths at /
-        let received_parts = route_params_unparsed.split('/').collect::<Vec<&str>>();
-        let defined_parts = handler.1.split('/').collect::<Vec<&str>>();
-
-        for (idx_received, (received_section, defined_section)) in received_parts.iter().zip(defined_parts.iter()).enumerate() {
-            if defined_section.starts_with(':') {
-                // handle treat the rest of the path as single param
-                if defined_section.ends_with('*') {
-                    found_handler = true;
-                    let paramcontent = received_parts[idx_received..].join("/");
-                    route_params.push((
-                        defined_section.to_owned().to_owned(),
-                        paramcontent
-                        //paramcontent.trim_end_matches('/').to_owned(),
-                    ));
-                    break;
-                // handle single param
-                } else {
-                    route_params.push((
-                        defined_section.to_owned().to_owned(),
-                        received_section.to_owned().to_owned(),
-                    ));
-                }
-                continue;
-            }
-            // does not match, break from inner loop
-            if received_section != defined_section {
-                break;
-            }
-        }
+    /// Sets `Cache-Control: no-store` on every response which does not define its own Cache-Control header
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.no_cache(true);
+    /// ```
+    pub fn no_cache(&mut self, enabled: bool) -> &mut Self {
+        self.no_cache = enabled;
+        self
+    }
 
-        // if there are parameters, add them to the request
-        if !route_params.is_empty() {
-            http_request.route_params = Some(route_params);
-        }
+    /// Sets how routes with and without a trailing slash are matched, defaults to [`TrailingSlash::Strict`]
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::{HttpServer, TrailingSlash};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// // a handler registered for /users will also serve /users/
+    /// server.trailing_slash(TrailingSlash::Lenient);
+    /// ```
+    pub fn trailing_slash(&mut self, policy: TrailingSlash) -> &mut Self {
+        self.trailing_slash = policy;
+        self
+    }
 
-        if handler.0 == http_request.http_headers.method {
-            found_handler = true;
-            #[cfg(feature = "log")]
-            log::debug!(
-                "Using handler {} for {} from {}",
-                handler.1,
-                http_request.http_headers.path,
-                stream.peer_addr().unwrap_or(std::net::SocketAddr::new(
-                    std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)),
-                    0000
-                ))
-            );
+    /// Compares literal route sections ignoring ASCII case, route params are passed on unchanged
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// // a handler registered for /users will also serve /Users
+    /// server.case_insensitive_routes(true);
+    /// ```
+    pub fn case_insensitive_routes(&mut self, enabled: bool) -> &mut Self {
+        self.case_insensitive_routes = enabled;
+        self
+    }
 
-            handle_closure(&mut stream, &http_request, &handler.2)?;
-        }
+    /// Limits how many new connections are accepted per second, 0 disables the limit.
+    /// Connections above the rate wait in the OS backlog until they are accepted
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// // one connection every 100ms
+    /// server.max_accept_rate(10);
+    /// ```
+    pub fn max_accept_rate(&mut self, per_sec: u32) -> &mut Self {
+        self.max_accept_rate = (per_sec > 0).then_some(per_sec);
+        self
     }
-    if !found_handler {
-        #[cfg(feature = "log")]
-        log::warn!("Could not find handler, using default");
 
-        handle_closure(&mut stream, &http_request, default_handler)?;
+    /// Emits an access log line in the given format at info level after every response, None disables it.
+    /// Defaults to [`AccessLogFormat::Common`]
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::{access_log::AccessLogFormat, server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.access_log(Some(AccessLogFormat::Json));
+    /// ```
+    #[cfg(feature = "log")]
+    pub fn access_log(&mut self, format: Option<AccessLogFormat>) -> &mut Self {
+        self.access_log = format;
+        self
     }
-    Ok(())
-}
 
-fn handle_closure(
-    stream: &mut TcpStream,
-    request: &HttpRequest,
-    exec: &HttpHandlerFn,
-) -> std::io::Result<()> {
-    let response = exec(request);
-    stream.write_all(response.to_headers().join("\r\n").as_bytes())?;
-    match response.data {
-        Some(data) => match data {
-            http_base::http::http_structs::HttpData::Bytes(vec) => stream.write_all(&vec),
-            http_base::http::http_structs::HttpData::Stream(mut read) => {
-                match io::copy(&mut read.0, stream) {
-                    Ok(_) => Ok(()),
-                    Err(err) => Err(err),
+    /// Enables CORS, adding the Access-Control-Allow-* headers to responses for allowed origins.
+    /// Preflight OPTIONS requests are answered with 204 without calling any handler
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::{cors::Cors, server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.cors(Cors {
+    ///     allowed_origins: vec![String::from("https://app.example")],
+    ///     allowed_methods: vec![String::from("GET"), String::from("POST")],
+    ///     ..Default::default()
+    /// });
+    /// ```
+    pub fn cors(&mut self, cors: Cors) -> &mut Self {
+        self.cors = Some(cors);
+        self
+    }
+
+    /// Answers every preflight request with 204 reflecting the requested origin, method and
+    /// headers, so no OPTIONS handlers are needed. Responses to cross origin requests get the
+    /// matching Access-Control-Allow-* headers, as browsers would block them otherwise.
+    /// This is `cors(Cors::permissive())` and is not meant for production, any website can make
+    /// credentialed requests to the server, configure `cors` with explicit origins instead
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.enable_cors_preflight();
+    /// ```
+    pub fn enable_cors_preflight(&mut self) -> &mut Self {
+        self.cors(Cors::permissive())
+    }
+
+    /// Answers HEAD requests without a matching HEAD handler with the GET handler of the route.
+    /// The response keeps all headers of the GET response, including Content-Length, but has no body
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpData, HttpResponse, HttpRequest, HttpStatus, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.auto_head(true);
+    /// // HEAD /page is answered with the headers of this response
+    /// server.get("/page".to_owned(), |_: &HttpRequest| {
+    ///     HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, Some(HttpData::Bytes(b"<h1>hi</h1>".to_vec())))
+    /// });
+    /// ```
+    pub fn auto_head(&mut self, enabled: bool) -> &mut Self {
+        self.auto_head = enabled;
+        self
+    }
+
+    /// Trusts the `X-Forwarded-For`, `X-Real-IP` and `X-Forwarded-Proto` headers sent by clients, which
+    /// is only safe behind a proxy setting them. When disabled the address headers are replaced with the
+    /// tcp peer address and `X-Forwarded-Proto` is removed
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.trust_proxy(true);
+    /// ```
+    pub fn trust_proxy(&mut self, enabled: bool) -> &mut Self {
+        self.trust_proxy = enabled;
+        self
+    }
+
+    /// Limits each client ip (see [`HttpRequestExt::real_ip`]) to requests_per_sec requests with bursts of
+    /// up to burst requests. Requests above the limit are answered with 429 Too Many Requests and Retry-After
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.with_rate_limit(10, 20);
+    /// ```
+    pub fn with_rate_limit(&mut self, requests_per_sec: u32, burst: u32) -> &mut Self {
+        self.rate_limiter = Some(RateLimiter::new(requests_per_sec, burst));
+        self
+    }
+
+    /// Adds an ETag computed from the body to every response without one, see [`HttpResponseExt::with_etag`].
+    /// GET and HEAD requests with a matching If-None-Match header are answered with 304 Not Modified,
+    /// which also happens for ETags set by handlers when this is disabled
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.etag(true);
+    /// ```
+    pub fn etag(&mut self, enabled: bool) -> &mut Self {
+        self.etag = enabled;
+        self
+    }
+
+    /// Adds a websocket handler to the server. GET requests to the path are upgraded with the
+    /// websocket handshake, after which the handler owns the connection and reads and writes the
+    /// frames itself. Requests without a valid handshake are answered with 400 Bad Request.
+    /// The handler blocks the thread serving the connection until it returns
+    /// Example:
+    /// ```rust
+    /// use std::net::TcpStream;
+    /// use http_serv::{HttpRequest, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.websocket("/chat".to_owned(), Box::new(|request: &HttpRequest, stream: TcpStream| {
+    ///     // read and write websocket frames on stream
+    /// }));
+    /// ```
+    pub fn websocket(&mut self, path: String, exec: StreamHandlerFn) -> &mut Self {
+        #[cfg(feature = "log")]
+        log::debug!("Adding websocket {path}");
+        self.stream_handlers.push((HttpMethod::GET, path, exec, true));
+        self
+    }
+
+    /// Adds a handler that takes over the connection instead of returning a response, e.g. for
+    /// server-sent events or long polling. The request is parsed, validated, rate limited and
+    /// routed as usual, but the server writes nothing afterwards: no status line, no headers and
+    /// no body. The handler writes the whole response itself and the connection is closed once it
+    /// drops the stream. Hijacking routes are matched before regular routes and are logged with
+    /// status 0 as the server does not know what was written.
+    /// CONNECT and TRACE are not HttpMethods and cannot be hijacked. CONNECT requests are handed
+    /// to the handler registered with `connect`, TRACE is answered with 405 Method Not Allowed, as
+    /// echoing a request back would hand cookies and credentials to any script able to send it.
+    /// Its Allow header lists the methods routed for the path and host
+    /// Example:
+    /// ```rust
+    /// use std::{io::Write, net::TcpStream};
+    /// use http_serv::{HttpMethod, HttpRequest, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.hijack(HttpMethod::GET, "/events".to_owned(), Box::new(|request: &HttpRequest, mut stream: TcpStream| {
+    ///     let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n");
+    ///     let _ = stream.write_all(b"data: hello\n\n");
+    /// }));
+    /// ```
+    pub fn hijack(&mut self, method: HttpMethod, path: String, exec: StreamHandlerFn) -> &mut Self {
+        #[cfg(feature = "log")]
+        log::debug!("Adding hijacking {method:?} {path}");
+        self.stream_handlers.push((method, path, exec, false));
+        self
+    }
+
+    /// Sets the handler taking over CONNECT requests, e.g. to tunnel them to the requested target.
+    /// CONNECT is not an HttpMethod, so instead of an HttpRequest the handler gets the request
+    /// target, e.g. `example.com:443`, and the headers. Like a hijacking handler it writes the
+    /// whole response itself. CONNECT requests are not routed, rate limited or access logged.
+    /// Without a handler CONNECT is answered with 501 Not Implemented
+    /// Example:
+    /// ```rust
+    /// use std::{io::{self, Write}, net::TcpStream, thread};
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.connect(Box::new(|target: &str, _headers: &[(String, String)], mut client: TcpStream| {
+    ///     let Ok(mut upstream) = TcpStream::connect(target) else {
+    ///         let _ = client.write_all(b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n");
+    ///         return;
+    ///     };
+    ///     let _ = client.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n");
+    ///     let (mut client_read, mut upstream_write) = (client.try_clone().unwrap(), upstream.try_clone().unwrap());
+    ///     thread::spawn(move || io::copy(&mut client_read, &mut upstream_write));
+    ///     let _ = io::copy(&mut upstream, &mut client);
+    /// }));
+    /// ```
+    pub fn connect(&mut self, exec: ConnectHandlerFn) -> &mut Self {
+        self.connect_handler = Some(exec);
+        self
+    }
+
+    /// Adds a handler that reads the request body itself instead of receiving it in
+    /// `request.data`, so large uploads can be written to disk without buffering them in memory.
+    /// The reader is bounded by Content-Length or decodes a chunked body, it fails with
+    /// `io::ErrorKind::UnexpectedEof` if the client sends less than announced. The request is
+    /// validated, rate limited and routed as usual. `handler_timeout` does not apply, as the
+    /// handler reads the body at the pace of the client. Up to 1 MiB of body the handler leaves
+    /// unread, e.g. when rejecting an upload early, is discarded before the response is sent, as
+    /// closing a connection with unread data resets it and the client may lose the response
+    /// Example:
+    /// ```rust
+    /// use std::{fs::File, io::{self, Read}};
+    /// use http_serv::{HttpMethod, HttpRequest, HttpResponse, HttpStatus, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.upload(HttpMethod::PUT, "/files/upload".to_owned(), |_: &HttpRequest, body: &mut dyn Read| {
+    ///     let status = File::create("upload.bin")
+    ///         .and_then(|mut file| io::copy(body, &mut file))
+    ///         .map_or(HttpStatus::InternalServerError, |_| HttpStatus::Created);
+    ///     HttpResponse::new(String::from("1.1"), status, None, None)
+    /// });
+    /// ```
+    pub fn upload(
+        &mut self,
+        method: HttpMethod,
+        path: String,
+        exec: impl Fn(&HttpRequest, &mut dyn Read) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        #[cfg(feature = "log")]
+        log::debug!("Adding upload {method:?} {path}");
+        self.body_handlers.push((method, path, Box::new(exec)));
+        self
+    }
+
+    /// Sets a check for requests sent with `Expect: 100-continue`, which is run on the request
+    /// line and headers before the client sends the body. Returning a response rejects the request
+    /// without reading the body, returning None lets the client continue. Without a check every
+    /// such request is continued. Expectations other than 100-continue are answered with
+    /// 417 Expectation Failed
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpHeaders, HttpResponse, HttpStatus, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.expect_continue(Box::new(|headers: &HttpHeaders| {
+    ///     let length = headers
+    ///         .extra_headers
+    ///         .iter()
+    ///         .find(|(key, _)| key.eq_ignore_ascii_case("Content-Length"))
+    ///         .and_then(|(_, value)| value.parse::<u64>().ok())?;
+    ///     (length > 1024 * 1024)
+    ///         .then(|| HttpResponse::new(String::from("1.1"), HttpStatus::PayloadTooLarge, None, None))
+    /// }));
+    /// ```
+    pub fn expect_continue(&mut self, exec: ExpectHandlerFn) -> &mut Self {
+        self.expect_continue = Some(exec);
+        self
+    }
+
+    /// Sets the maximum number of header lines in a request, defaults to 100.
+    /// Requests with more headers are answered with 431 Request Header Fields Too Large.
+    /// Heads that cannot be parsed are answered with 400, heads of protocol versions other than
+    /// HTTP/1.0 and HTTP/1.1 with 505 and heads not complete within `head_timeout` with 408
+    /// Request Timeout, so slow clients cannot get around the limits
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.max_headers(50);
+    /// ```
+    pub fn max_headers(&mut self, count: usize) -> &mut Self {
+        self.head_limits.max_headers = count;
+        self
+    }
+
+    /// Sets the maximum length in bytes of the request line and of each header line, defaults to 8 KiB.
+    /// Requests with longer lines are answered with 431 Request Header Fields Too Large
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.max_header_line(4 * 1024);
+    /// ```
+    pub fn max_header_line(&mut self, length: usize) -> &mut Self {
+        self.head_limits.max_line_length = length;
+        self
+    }
+
+    /// Sets how long the complete request head may take to arrive, defaults to 10 seconds.
+    /// Requests whose head is not complete by then are answered with 408 Request Timeout
+    /// Example:
+    /// ```rust
+    /// use std::time::Duration;
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.head_timeout(Duration::from_secs(5));
+    /// ```
+    pub fn head_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.head_limits.timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum size in bytes of a request body, defaults to 10 MiB. Requests declaring a
+    /// larger Content-Length are answered with 413 Payload Too Large before the body is read,
+    /// clients waiting for `100 Continue` do not send it at all. Chunked bodies are cut off once
+    /// they grow past the limit. Bodies streamed to `upload` handlers are not limited
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.max_body_size(1024 * 1024);
+    /// ```
+    pub fn max_body_size(&mut self, size: u64) -> &mut Self {
+        self.body_limits.max_size = size;
+        self
+    }
+
+    /// Sets how long the complete request body may take to arrive once the head has been read,
+    /// defaults to 60 seconds. Requests whose body is not complete by then are answered with 408
+    /// Request Timeout. Bodies streamed to `upload` handlers are not limited
+    /// Example:
+    /// ```rust
+    /// use std::time::Duration;
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.body_timeout(Duration::from_secs(30));
+    /// ```
+    pub fn body_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.body_limits.timeout = timeout;
+        self
+    }
+
+    /// Enables adding the trailer fields of chunked request bodies to the request headers,
+    /// disabled by default. Only trailers the client announced in its `Trailer` header are
+    /// added, fields like Content-Length or Host that RFC 9110 does not allow in trailers never
+    /// are. Without it, and for bodies streamed to `upload` handlers, trailers are dropped
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpRequest, HttpRequestExt, HttpResponse, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.chunked_trailers(true);
+    /// server.post("/blobs".to_owned(), |request: &HttpRequest| {
+    ///     let _checksum = request.header("X-Checksum");
+    ///     HttpResponse::default()
+    /// });
+    /// ```
+    pub fn chunked_trailers(&mut self, enabled: bool) -> &mut Self {
+        self.chunked_trailers = enabled;
+        self
+    }
+
+    /// Sets the maximum number of `&` separated query parameters, defaults to 1000. Requests with
+    /// more are answered with 400 Bad Request before the query is parsed. The length of the query
+    /// is bounded by `max_header_line`
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.max_query_params(100);
+    /// ```
+    pub fn max_query_params(&mut self, count: usize) -> &mut Self {
+        self.max_query_params = count;
+        self
+    }
+
+    /// Sets the Server header added to every response that has none, None (the default) adds no
+    /// Server header so the server does not advertise itself
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.server_header(Some(String::from("http_serv")));
+    /// ```
+    pub fn server_header(&mut self, server: Option<String>) -> &mut Self {
+        self.server_header = server;
+        self
+    }
+
+    /// Sets whether a Date header with the current time is added to every response that has none,
+    /// defaults to true as HTTP/1.1 servers with a clock are required to send it
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.date_header(false);
+    /// ```
+    pub fn date_header(&mut self, enabled: bool) -> &mut Self {
+        self.date_header = enabled;
+        self
+    }
+
+    /// Sets a handler for the responses the server sends itself when rejecting a request with the
+    /// given status, e.g. to return a branded error page. The handler receives the generated
+    /// response including headers like Retry-After or Allow and returns the one to send. This
+    /// covers 400 for malformed requests and websocket handshakes, 405 and 501 for unknown methods,
+    /// 408 for heads or bodies not arriving in time, 413 for bodies over `max_body_size`, 417 for
+    /// unknown expectations, 429 when rate limited, 431 for oversized heads, 500 for panicking
+    /// handlers, 504 for handlers timing out with `handler_timeout`, 503 for rejected connections
+    /// and 505 for unsupported protocol versions. Responses returned by route handlers are not passed
+    /// through it, setting a status again replaces its handler. If the handler panics the
+    /// generated response is sent without headers and body
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpData, HttpResponse, HttpResponseExt, HttpStatus, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.on_error(HttpStatus::RequestHeaderFieldsTooLarge, |mut response: HttpResponse| {
+    ///     response.set_header("Content-Type", "text/html");
+    ///     response.data = Some(HttpData::Bytes(b"<h1>Request headers too large</h1>".to_vec()));
+    ///     response
+    /// });
+    /// ```
+    pub fn on_error(
+        &mut self,
+        status: HttpStatus,
+        exec: impl Fn(HttpResponse) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        let status = status.code();
+        self.error_handlers.retain(|(code, _)| *code != status);
+        self.error_handlers.push((status, Box::new(exec)));
+        self
+    }
+
+    /// Sets a hook receiving the serialized status line and headers of every response, including
+    /// the blank line ending them, and returning the bytes written instead. The body is written
+    /// unchanged after them, so framing headers like Content-Length have to stay intact. Meant for
+    /// debugging the exact wire output or non-standard framing, use handlers or `on_error` to
+    /// change responses
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.on_raw_response(|head: &[u8]| {
+    ///     eprint!("{}", String::from_utf8_lossy(head));
+    ///     head.to_vec()
+    /// });
+    /// ```
+    pub fn on_raw_response(
+        &mut self,
+        exec: impl Fn(&[u8]) -> Vec<u8> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.raw_response = Some(Box::new(exec));
+        self
+    }
+
+    /// Sets how long a handler may take before the client is answered with 504 Gateway Timeout.
+    /// Handlers then run on a separate thread per request. As handlers are synchronous they
+    /// cannot be cancelled, a timed out handler keeps running until it returns and its response
+    /// is discarded. How many of those threads may run at once is limited by
+    /// `max_handler_threads`. Websocket and hijacking handlers are not affected
+    /// Example:
+    /// ```rust
+    /// use std::time::Duration;
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.handler_timeout(Duration::from_secs(10));
+    /// ```
+    pub fn handler_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.handler_timeout = Some(timeout);
+        self
+    }
+
+    /// Limits how many handler threads spawned for `handler_timeout` may run at once, defaults
+    /// to 256. Timed out handlers count until they return, so handlers that hang cannot pile up
+    /// threads without bound. Requests beyond the limit are answered with 503 Service Unavailable
+    /// Example:
+    /// ```rust
+    /// use std::time::Duration;
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.handler_timeout(Duration::from_secs(10)).max_handler_threads(64);
+    /// ```
+    pub fn max_handler_threads(&mut self, max: usize) -> &mut Self {
+        self.max_handler_threads = max;
+        self
+    }
+
+    /// Sets whether TCP_NODELAY is set on accepted connections, defaults to true.
+    /// This disables Nagle's algorithm, so small responses are sent without waiting for more data.
+    /// The listener needs no SO_REUSEADDR option, the standard library already sets it on unix
+    /// when binding, so a restarted server can bind while old connections are in TIME_WAIT
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.tcp_nodelay(false);
+    /// ```
+    pub fn tcp_nodelay(&mut self, enabled: bool) -> &mut Self {
+        self.tcp_nodelay = enabled;
+        self
+    }
+
+    /// Sets how many connections the operating system queues before they are accepted, applied when
+    /// run_loop starts. Connections beyond it are refused by the operating system, which also caps
+    /// the value, e.g. at `net.core.somaxconn` on linux. Without it the standard library default is kept
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.listen_backlog(1024);
+    /// ```
+    pub fn listen_backlog(&mut self, backlog: u32) -> &mut Self {
+        self.listen_backlog = Some(backlog);
+        self
+    }
+
+    /// Returns a scope to register handlers that only serve requests whose Host header matches
+    /// host, ignoring case and port. For a matching host, all of its routes including wildcard
+    /// routes are tried first, then the routes registered on the server itself, then the
+    /// default handler. Requests for other hosts never reach host scoped routes
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpResponse, HttpRequest, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server
+    ///     .host("api.example.com")
+    ///     .get("/users".to_owned(), Box::new(|_: &HttpRequest| HttpResponse::default()));
+    /// ```
+    pub fn host(&mut self, host: &str) -> HostScope<'_> {
+        HostScope {
+            server: self,
+            host: host.to_owned(),
+        }
+    }
+
+    /// Returns the method and path pattern of every registered handler in registration order,
+    /// followed by the websocket and hijacking routes and then the upload routes. Host scoped
+    /// routes are not included
+    /// Example:
+    /// ```rust
+    /// use std::io::Read;
+    /// use http_serv::{HttpMethod, HttpRequest, HttpResponse, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.upload(HttpMethod::PUT, "/files".to_owned(), |_: &HttpRequest, _: &mut dyn Read| HttpResponse::default());
+    /// server.get("/users".to_owned(), Box::new(|_: &HttpRequest| HttpResponse::default()));
+    /// assert_eq!(server.routes(), vec![(HttpMethod::GET, "/users"), (HttpMethod::PUT, "/files")]);
+    /// ```
+    pub fn routes(&self) -> Vec<(HttpMethod, &str)> {
+        self.handlers
+            .iter()
+            .map(|handler| (handler.0.clone(), handler.1.as_str()))
+            .chain(
+                self.stream_handlers
+                    .iter()
+                    .map(|handler| (handler.0.clone(), handler.1.as_str())),
+            )
+            .chain(
+                self.body_handlers
+                    .iter()
+                    .map(|handler| (handler.0.clone(), handler.1.as_str())),
+            )
+            .collect()
+    }
+
+    /// Returns the routes that can never be reached, because a route registered for the same
+    /// method and path is matched first. Among routes of the same kind the one registered first
+    /// wins. Upload routes are matched before websocket and hijacking routes, which are matched
+    /// before regular handlers, whatever the registration order. Routes scoped to a host only
+    /// shadow routes of the same host, but are themselves shadowed by upload, websocket and
+    /// hijacking routes, as those apply to every host. With the `log` feature every shadowed route
+    /// is logged as a warning when `run_loop` starts
+    /// Example:
+    /// ```rust
+    /// use std::net::TcpStream;
+    /// use http_serv::{HttpMethod, HttpRequest, HttpResponse, http_server::server::{HttpServer, RouteKind, ShadowedRoute}};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.get("/users".to_owned(), |_: &HttpRequest| HttpResponse::default());
+    /// assert!(server.shadowed_routes().is_empty());
+    ///
+    /// // the second handler never runs
+    /// server.get("/users".to_owned(), |_: &HttpRequest| HttpResponse::default());
+    /// // the websocket route is matched first although it was registered later
+    /// server.get("/chat".to_owned(), |_: &HttpRequest| HttpResponse::default());
+    /// server.websocket("/chat".to_owned(), Box::new(|_: &HttpRequest, _: TcpStream| {}));
+    /// // the same route for another host is no duplicate
+    /// server.host("api.example.com").get("/users".to_owned(), |_: &HttpRequest| HttpResponse::default());
+    /// assert_eq!(server.shadowed_routes(), vec![
+    ///     ShadowedRoute { method: HttpMethod::GET, path: "/users", host: None, kind: RouteKind::Handler, shadowed_by: RouteKind::Handler },
+    ///     ShadowedRoute { method: HttpMethod::GET, path: "/chat", host: None, kind: RouteKind::Handler, shadowed_by: RouteKind::Websocket },
+    /// ]);
+    ///
+    /// server.host("api.example.com").get("/chat".to_owned(), |_: &HttpRequest| HttpResponse::default());
+    /// assert!(server.shadowed_routes().contains(&ShadowedRoute {
+    ///     method: HttpMethod::GET,
+    ///     path: "/chat",
+    ///     host: Some("api.example.com"),
+    ///     kind: RouteKind::Handler,
+    ///     shadowed_by: RouteKind::Websocket,
+    /// }));
+    /// ```
+    pub fn shadowed_routes(&self) -> Vec<ShadowedRoute<'_>> {
+        // Host Method Path Kind, in registration order per kind
+        let routes: Vec<(Option<&str>, &HttpMethod, &str, RouteKind)> = self
+            .body_handlers
+            .iter()
+            .map(|handler| (None, &handler.0, handler.1.as_str(), RouteKind::Upload))
+            .chain(self.stream_handlers.iter().map(|handler| {
+                let kind = match handler.3 {
+                    true => RouteKind::Websocket,
+                    false => RouteKind::Hijack,
+                };
+                (None, &handler.0, handler.1.as_str(), kind)
+            }))
+            .chain(self.host_handlers.iter().map(|(host, handler)| {
+                (Some(host.as_str()), &handler.0, handler.1.as_str(), RouteKind::Handler)
+            }))
+            .chain(
+                self.handlers
+                    .iter()
+                    .map(|handler| (None, &handler.0, handler.1.as_str(), RouteKind::Handler)),
+            )
+            .collect();
+        let same_path = |a: &str, b: &str| match self.case_insensitive_routes {
+            true => a.eq_ignore_ascii_case(b),
+            false => a == b,
+        };
+        let mut shadowed = Vec::new();
+        for (idx, &(host, method, path, kind)) in routes.iter().enumerate() {
+            // routes are listed in matching order, so only earlier ones can shadow this one
+            let winner = routes[..idx].iter().find(|(other_host, other_method, other_path, other_kind)| {
+                other_method == &method
+                    && same_path(other_path, path)
+                    && (other_host == &host || other_kind.precedence() < RouteKind::Handler.precedence())
+            });
+            if let Some(winner) = winner {
+                shadowed.push(ShadowedRoute {
+                    method: method.clone(),
+                    path,
+                    host,
+                    kind,
+                    shadowed_by: winner.3,
+                });
+            }
+        }
+        shadowed
+    }
+
+    /// Sets how long `shutdown` waits for connections being served, including accepted ones still
+    /// waiting for a worker. Once the deadline passed the remaining connections are closed, which
+    /// makes handlers reading or writing them fail. Handlers blocked on anything else only give up
+    /// their worker with `handler_timeout`. Without a timeout shutdown waits indefinitely
+    /// Example:
+    /// ```rust
+    /// use std::time::Duration;
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.shutdown_timeout(Duration::from_secs(30));
+    /// ```
+    pub fn shutdown_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.shutdown_timeout = Some(timeout);
+        self
+    }
+
+    /// Stops accepting connections, `run_loop` returns once the connections being served finished
+    /// or were closed at the `shutdown_timeout` deadline. Called from another thread sharing the
+    /// server, e.g. through an Arc
+    /// Example:
+    /// ```rust
+    /// use std::{sync::Arc, thread, time::Duration};
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("127.0.0.1".to_string(), "0".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("127.0.0.1".to_string(), "0".to_string(), Vec::new(), None).unwrap();
+    /// server.shutdown_timeout(Duration::from_secs(5));
+    /// let server = Arc::new(server);
+    /// let handle = Arc::clone(&server);
+    /// thread::spawn(move || {
+    ///     thread::sleep(Duration::from_millis(100));
+    ///     handle.shutdown();
+    /// });
+    /// server.run_loop().unwrap();
+    /// ```
+    pub fn shutdown(&self) {
+        if !self.drain.request() {
+            return;
+        }
+        #[cfg(feature = "log")]
+        log::info!("Shutting down");
+        if let Some(timeout) = self.shutdown_timeout {
+            let drain = Arc::clone(&self.drain);
+            let deadline = thread::Builder::new()
+                .name(String::from("http-shutdown"))
+                .spawn(move || {
+                    thread::sleep(timeout);
+                    drain.expire();
+                });
+            if let Err(_err) = deadline {
+                #[cfg(feature = "log")]
+                log::error!("Failed to start shutdown deadline: {_err}");
+            }
+        }
+        // accept blocks until the next connection arrives, connecting wakes the loops up
+        for listener in &self.listeners {
+            let Ok(mut addr) = listener.local_addr() else {
+                continue;
+            };
+            if addr.ip().is_unspecified() {
+                addr.set_ip(match addr.ip() {
+                    IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                    IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+                });
+            }
+            let _ = TcpStream::connect_timeout(&addr, Duration::from_secs(1));
+        }
+    }
+
+    /// Returns the address the server is listening on, e.g. to find the port picked for port 0.
+    /// Listeners added with `add_listener` are not included
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let server = HttpServer::new("127.0.0.1".to_string(), "0".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let server = HttpServer::example("127.0.0.1".to_string(), "0".to_string(), Vec::new(), None).unwrap();
+    /// assert_ne!(server.local_addr().unwrap().port(), 0);
+    /// ```
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listeners[0].local_addr()
+    }
+
+    /// Binds an additional address the server accepts connections on, e.g. to serve port 80 and
+    /// 8080 with the same routes. Every listener has its own accept loop feeding the same workers,
+    /// `max_accept_rate` applies to each of them separately. Without the threading feature every
+    /// listener serves its connections one at a time on its own thread
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("127.0.0.1".to_string(), "0".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("127.0.0.1".to_string(), "0".to_string(), Vec::new(), None).unwrap();
+    /// server.add_listener("127.0.0.1".to_string(), "0".to_string()).unwrap();
+    /// ```
+    pub fn add_listener(&mut self, addr: String, port: String) -> io::Result<&mut Self> {
+        self.listeners.push(TcpListener::bind(format!("{addr}:{port}"))?);
+        Ok(self)
+    }
+
+    /// Returns a snapshot of the request, response and connection counters. The server can be
+    /// shared with another thread while run_loop is running, e.g. through an Arc, to poll them
+    /// Example:
+    /// ```rust
+    /// use http_serv::http_server::server::HttpServer;
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// let metrics = server.metrics();
+    /// assert_eq!(metrics.total_requests, 0);
+    /// assert_eq!(metrics.active_connections, 0);
+    /// ```
+    pub fn metrics(&self) -> Metrics {
+        self.counters.snapshot()
+    }
+
+    /// Adds a get handler at path serving the counters in the Prometheus text exposition format,
+    /// including the requests per route template and status code
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpMethod, http_server::server::HttpServer};
+    ///
+    /// # #[cfg(not(feature = "threading"))]
+    /// let mut server = HttpServer::new("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// # #[cfg(feature = "threading")]
+    /// # let mut server = HttpServer::example("0.0.0.0".to_string(), "8443".to_string(), Vec::new(), None).unwrap();
+    /// server.enable_metrics_route("/metrics");
+    /// assert_eq!(server.routes(), vec![(HttpMethod::GET, "/metrics")]);
+    /// ```
+    pub fn enable_metrics_route(&mut self, path: &str) -> &mut Self {
+        let counters = Arc::clone(&self.counters);
+        self.get(path.to_owned(), move |_: &HttpRequest| {
+            let mut response = HttpResponse::new(
+                String::from("1.1"),
+                HttpStatus::Ok,
+                None,
+                Some(HttpData::Bytes(counters.render_prometheus().into_bytes())),
+            );
+            response.set_header("Content-Type", "text/plain; version=0.0.4");
+            response.no_cache()
+        })
+    }
+
+    fn handle_stream(&self, mut stream: TcpStream) {
+        let _connection = self.counters.connection();
+        let Some(_tracked) = self.drain.track(&stream) else {
+            #[cfg(feature = "log")]
+            log::debug!("Shutdown deadline passed, dropping queued connection");
+            return;
+        };
+        if let Err(_err) = stream.set_nodelay(self.tcp_nodelay) {
+            #[cfg(feature = "log")]
+            log::debug!("Failed to set TCP_NODELAY: {_err}");
+        }
+        #[cfg(feature = "log")]
+        let start = Instant::now();
+        let (http_headers, raw_head) = match self.check_head(&mut stream) {
+            Ok(Some(head)) => head,
+            Ok(None) => return,
+            Err(_err) => {
+                #[cfg(feature = "log")]
+                log::debug!("Failed to check request head: {_err}");
+                return;
+            }
+        };
+        let body_handler = self.find_body_handler(&http_headers);
+        let mut http_request = HttpRequest {
+            http_headers,
+            data: None,
+            route_params: None,
+            query_params: None,
+        };
+        // with a body handler the body stays on the stream until the handler reads it
+        if body_handler.is_none() {
+            let headers = &http_request.http_headers.extra_headers;
+            let status = match read_request_body(&stream, headers, self.body_limits) {
+                Ok(RequestBody::Body(Some(body))) => {
+                    if self.chunked_trailers {
+                        let trailers = declared_trailers(headers, body.trailers);
+                        http_request.http_headers.extra_headers.extend(trailers);
+                    }
+                    http_request.data = Some(body.data);
+                    None
+                }
+                Ok(RequestBody::Body(None)) => None,
+                Ok(RequestBody::TooLarge) => Some(HttpStatus::PayloadTooLarge),
+                Ok(RequestBody::Malformed) => Some(HttpStatus::BadRequest),
+                Ok(RequestBody::TimedOut) => Some(HttpStatus::RequestTimeout),
+                Err(_err) => {
+                    #[cfg(feature = "log")]
+                    log::error!("Failed to read request body: {_err}");
+                    return;
+                }
+            };
+            if let Some(status) = status {
+                #[cfg(feature = "log")]
+                log::warn!("Rejecting request body with {}", status.code());
+                let response =
+                    self.rejection(HttpResponse::new(String::from("1.1"), status, None, None));
+                if let Err(_err) = self.write_response(&mut stream, None, response) {
+                    #[cfg(feature = "log")]
+                    log::debug!("Failed to reject request body: {_err}");
+                }
+                return;
+            }
+        }
+        self.counters.request();
+        // forwarding headers are only kept if the proxy in front of the server sets them
+        if !self.trust_proxy {
+            http_request.remove_header("X-Forwarded-For");
+            http_request.remove_header("X-Real-IP");
+            http_request.remove_header("X-Forwarded-Proto");
+        }
+        // the peer can be unknown for a moment, e.g. right after the client reset the connection.
+        // The request is still served, real_ip then only has forwarded headers to go by
+        let peer = stream
+            .peer_addr()
+            .map_err(|_err| {
+                #[cfg(feature = "log")]
+                log::warn!("Failed to get peer address, serving request without client ip: {_err}");
+            })
+            .ok();
+        let local = stream
+            .local_addr()
+            .map_err(|_err| {
+                #[cfg(feature = "log")]
+                log::debug!("Failed to get local address: {_err}");
+            })
+            .ok();
+        let connection = ConnectionInfo { peer, local };
+        #[cfg(feature = "log")]
+        let (peer_name, method, path, protocol) = (
+            peer.map_or_else(|| String::from("unknown"), |peer| peer.to_string()),
+            format!("{:?}", http_request.http_headers.method),
+            http_request.http_headers.path.clone(),
+            // logged as in the request line, whether or not the version carries the prefix
+            format!("HTTP/{}", http_version(&http_request.http_headers.protocol)),
+        );
+        #[cfg(feature = "log")]
+        log::debug!("[{peer_name}]: {method} {path}");
+        // kept to answer with 500 if the handler panics, it takes the stream itself
+        let reply = stream.try_clone();
+        // a panicking handler only loses its own connection, the worker keeps serving
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            with_connection(connection, || {
+                self.handle_connection(stream, http_request, raw_head, connection, body_handler)
+            })
+        }));
+        let Ok(result) = result else {
+            #[cfg(feature = "log")]
+            log::error!(
+                "Thread {} panicked handling {method} {path} from {peer_name}",
+                thread::current().name().unwrap_or("unnamed")
+            );
+            // answered as with handler_timeout, where the panic ends the handler thread instead
+            let response = self.rejection(HttpResponse::new(
+                String::from("1.1"),
+                HttpStatus::InternalServerError,
+                None,
+                None,
+            ));
+            let written =
+                reply.and_then(|mut reply| self.write_response(&mut reply, None, response));
+            if let Err(_err) = written {
+                #[cfg(feature = "log")]
+                log::debug!("Failed to answer panicked handler with 500: {_err}");
+            }
+            return;
+        };
+        match result {
+            Ok(_info) => {
+                #[cfg(feature = "log")]
+                if let Some(format) = self.access_log {
+                    let entry = AccessLogEntry {
+                        peer,
+                        method: &method,
+                        path: &path,
+                        protocol: &protocol,
+                        status: _info.status,
+                        bytes: _info.bytes,
+                        duration: start.elapsed(),
+                    };
+                    log::info!("{}", entry.format(format));
+                }
+            }
+            // the client went away before the response was written completely
+            Err(_err) if is_disconnect(&_err) => {
+                #[cfg(feature = "log")]
+                log::debug!("Client disconnected: {_err}");
+            }
+            Err(_err) => {
+                #[cfg(feature = "log")]
+                log::error!("Encountered error handling connection: {_err}");
+            }
+        };
+    }
+
+    /// Reads the request head within the header limits and answers `Expect` headers before the
+    /// body is read, as clients sending `Expect: 100-continue` wait for the interim response.
+    /// Returns None if the request was rejected, otherwise the head parsed and as received
+    fn check_head(
+        &self,
+        stream: &mut TcpStream,
+    ) -> std::io::Result<Option<(HttpHeaders, Vec<u8>)>> {
+        let (head, raw) = read_head(stream, self.head_limits)?;
+        let head = match head {
+            RequestHead::Head(head) => head,
+            RequestHead::TooLarge => {
+                #[cfg(feature = "log")]
+                log::warn!("Rejecting request with oversized headers");
+                let response = self.rejection(HttpResponse::new(
+                    String::from("1.1"),
+                    HttpStatus::RequestHeaderFieldsTooLarge,
+                    None,
+                    None,
+                ));
+                self.write_rejection(stream, response)?;
+                return Ok(None);
+            }
+            RequestHead::UnsupportedMethod {
+                method,
+                path,
+                headers,
+            } => {
+                if let (true, Some(exec)) = (method == "CONNECT", &self.connect_handler) {
+                    self.handle_connect(stream, &path, &headers, exec)?;
+                    return Ok(None);
+                }
+                let host = headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case("Host"))
+                    .and_then(|(_, value)| split_host_port(value.trim()))
+                    .map(|(host, _)| host);
+                let response = self.rejection(self.reject_method(&method, &path, host));
+                self.write_rejection(stream, response)?;
+                return Ok(None);
+            }
+            RequestHead::Malformed(err) => {
+                #[cfg(feature = "log")]
+                log::warn!("Rejecting malformed request head: {err}");
+                let response =
+                    self.rejection(HttpResponse::new(String::from("1.1"), err.status(), None, None));
+                self.write_rejection(stream, response)?;
+                return Ok(None);
+            }
+            RequestHead::TimedOut => {
+                #[cfg(feature = "log")]
+                log::debug!("Request head did not arrive in time");
+                let response = self.rejection(HttpResponse::new(
+                    String::from("1.1"),
+                    HttpStatus::RequestTimeout,
+                    None,
+                    None,
+                ));
+                self.write_rejection(stream, response)?;
+                return Ok(None);
+            }
+            RequestHead::Closed => return Ok(None),
+        };
+        // rejected before 100 Continue, so the client does not send a body that is not read
+        if let Err(err) = is_chunked(&head.extra_headers) {
+            #[cfg(feature = "log")]
+            log::warn!("Rejecting request: {err}");
+            let status = match err.kind() {
+                io::ErrorKind::Unsupported => HttpStatus::NotImplemented,
+                _ => HttpStatus::BadRequest,
+            };
+            let response =
+                self.rejection(HttpResponse::new(String::from("1.1"), status, None, None));
+            self.write_rejection(stream, response)?;
+            return Ok(None);
+        }
+        let declared_length = match content_length(&head.extra_headers) {
+            Ok(length) => length,
+            Err(_err) => {
+                #[cfg(feature = "log")]
+                log::warn!("Rejecting request: {_err}");
+                let response = self.rejection(HttpResponse::new(
+                    String::from("1.1"),
+                    HttpStatus::BadRequest,
+                    None,
+                    None,
+                ));
+                self.write_rejection(stream, response)?;
+                return Ok(None);
+            }
+        };
+        if declared_length.is_some_and(|length| length > self.body_limits.max_size)
+            && self.find_body_handler(&head).is_none()
+        {
+            #[cfg(feature = "log")]
+            log::warn!("Rejecting request body larger than {} bytes", self.body_limits.max_size);
+            let response = self.rejection(HttpResponse::new(
+                String::from("1.1"),
+                HttpStatus::PayloadTooLarge,
+                None,
+                None,
+            ));
+            self.write_rejection(stream, response)?;
+            return Ok(None);
+        }
+        let Some((_, expect)) = head
+            .extra_headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("Expect"))
+        else {
+            return Ok(Some((head, raw)));
+        };
+        // HTTP/1.0 clients do not wait for the interim response
+        if http_version(&head.protocol) == "1.0" {
+            return Ok(Some((head, raw)));
+        }
+        let response = if expect.eq_ignore_ascii_case("100-continue") {
+            self.expect_continue.as_ref().and_then(|exec| {
+                // runs outside the handler's catch_unwind, a panic must not take down the worker
+                panic::catch_unwind(AssertUnwindSafe(|| exec(&head))).unwrap_or_else(|_| {
+                    #[cfg(feature = "log")]
+                    log::error!("Expect check panicked for {}", head.path);
+                    Some(self.rejection(HttpResponse::new(
+                        String::from("1.1"),
+                        HttpStatus::InternalServerError,
+                        None,
+                        None,
+                    )))
+                })
+            })
+        } else {
+            Some(self.rejection(HttpResponse::new(
+                String::from("1.1"),
+                HttpStatus::ExpectationFailed,
+                None,
+                None,
+            )))
+        };
+        match response {
+            Some(response) => {
+                #[cfg(feature = "log")]
+                log::debug!("Rejecting expectation {expect} for {}", head.path);
+                self.write_rejection(stream, response)?;
+                Ok(None)
+            }
+            None => {
+                stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+                Ok(Some((head, raw)))
+            }
+        }
+    }
+
+    /// Writes a response rejecting a request and discards what the client still sends for a
+    /// moment. The response carries Connection: close and write_response shut down the write
+    /// side, closing with the request body unread would reset the connection before the client
+    /// has read the response
+    fn write_rejection(&self, stream: &mut TcpStream, response: HttpResponse) -> io::Result<()> {
+        self.write_response(stream, None, response)?;
+        if let Err(_err) = discard_input(stream, MAX_UNREAD_BODY, REJECT_DISCARD_TIMEOUT) {
+            #[cfg(feature = "log")]
+            log::debug!("Stopped discarding rejected request: {_err}");
+        }
+        Ok(())
+    }
+
+    /// Passes a response the server sends itself through the error handler for its status. A
+    /// panicking error handler is replaced by a bare response with the same status, as rejections
+    /// are also sent outside of the handler's catch_unwind, e.g. from the accept loop
+    fn rejection(&self, response: HttpResponse) -> HttpResponse {
+        let status = response.status;
+        match self.error_handlers.iter().find(|(code, _)| *code == status.code()) {
+            Some((_, exec)) => panic::catch_unwind(AssertUnwindSafe(|| exec(response))).unwrap_or_else(|_| {
+                #[cfg(feature = "log")]
+                log::error!("Error handler for {} panicked", status.code());
+                HttpResponse::new(String::from("1.1"), status, None, None)
+            }),
+            None => response,
+        }
+    }
+
+    /// Hands a CONNECT request to the connect handler, which owns the connection from then on
+    fn handle_connect(
+        &self,
+        stream: &TcpStream,
+        target: &str,
+        headers: &[(String, String)],
+        exec: &ConnectHandlerFn,
+    ) -> std::io::Result<()> {
+        #[cfg(feature = "log")]
+        log::debug!("Handing CONNECT {target} to the connect handler");
+        self.counters.request();
+        let stream = stream.try_clone()?;
+        // a panicking handler only loses its own connection, as for the other handlers
+        if panic::catch_unwind(AssertUnwindSafe(|| exec(target, headers, stream))).is_err() {
+            #[cfg(feature = "log")]
+            log::error!("Connect handler panicked handling CONNECT {target}");
+        }
+        Ok(())
+    }
+
+    /// Answers a method HttpMethod does not know, which could not be routed. TRACE is refused with
+    /// 405 listing the methods routed for the path on host, as echoing the request would expose
+    /// credentials to scripts, everything else gets 501
+    fn reject_method(&self, method: &str, path: &str, host: Option<&str>) -> HttpResponse {
+        #[cfg(feature = "log")]
+        log::debug!("Rejecting unsupported method {method} for {path}");
+        if method != "TRACE" {
+            return HttpResponse::new(String::from("1.1"), HttpStatus::NotImplemented, None, None);
+        }
+        let route = path.split_once('?').map_or(path, |(route, _)| route);
+        let routes = |method: &HttpMethod| {
+            let stream_or_upload = self
+                .stream_handlers
+                .iter()
+                .map(|handler| (&handler.0, &handler.1))
+                .chain(self.body_handlers.iter().map(|handler| (&handler.0, &handler.1)))
+                .any(|(handler_method, handler_path)| {
+                    handler_method == method
+                        && match_route(handler_path, route, self.case_insensitive_routes).is_some()
+                });
+            stream_or_upload || self.find_handler(method, route, host).is_some()
+        };
+        let allow: Vec<&str> = [
+            HttpMethod::GET,
+            HttpMethod::HEAD,
+            HttpMethod::POST,
+            HttpMethod::PUT,
+            HttpMethod::DELETE,
+            HttpMethod::OPTIONS,
+            HttpMethod::PATCH,
+        ]
+        .iter()
+        .filter(|method| routes(method))
+        .map(HttpMethodExt::as_str)
+        .collect();
+        let mut response =
+            HttpResponse::new(String::from("1.1"), HttpStatus::MethodNotAllowed, None, None);
+        response.set_header("Allow", &allow.join(", "));
+        response
+    }
+
+    fn handle_connection(
+        &self,
+        mut stream: TcpStream,
+        mut http_request: HttpRequest,
+        raw_head: Vec<u8>,
+        connection: ConnectionInfo,
+        body_handler: Option<&BodyHandler>,
+    ) -> std::io::Result<ResponseInfo> {
+        // malformed paths never reach the handlers
+        if !is_valid_path(&http_request.http_headers.path, &http_request.http_headers.method) {
+            #[cfg(feature = "log")]
+            log::warn!("Rejecting malformed path {:?}", http_request.http_headers.path);
+            let response =
+                self.rejection(HttpResponse::new(String::from("1.1"), HttpStatus::BadRequest, None, None));
+            return self.write_response(&mut stream, Some(&http_request), response);
+        }
+
+        // HTTP/1.1 requires a Host header
+        if http_version(&http_request.http_headers.protocol) == "1.1" && http_request.host().is_none() {
+            #[cfg(feature = "log")]
+            log::warn!("Rejecting HTTP/1.1 request without Host header");
+            let response =
+                self.rejection(HttpResponse::new(String::from("1.1"), HttpStatus::BadRequest, None, None));
+            return self.write_response(&mut stream, Some(&http_request), response);
+        }
+
+        if let (Some(rate_limiter), Some(ip)) = (&self.rate_limiter, http_request.real_ip()) {
+            if let Err(retry_after) = rate_limiter.check(ip) {
+                #[cfg(feature = "log")]
+                log::debug!("Rate limiting {ip}");
+                let mut response =
+                    HttpResponse::new(String::from("1.1"), HttpStatus::TooManyRequests, None, None);
+                // whole seconds, rounded up so the client does not retry too early
+                let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                response.set_header("Retry-After", &retry_after.to_string());
+                let response = self.rejection(response);
+                return self.write_response(&mut stream, Some(&http_request), response);
+            }
+        }
+
+        if let Some(cors) = self.cors.as_ref().filter(|_| Cors::is_preflight(&http_request)) {
+            let response = cors.preflight(&http_request);
+            return self.write_response(&mut stream, None, response);
+        }
+
+        // split route and query params and parse query
+        let (route, query) = match http_request.http_headers.path.split_once('?') {
+            Some((route, query)) => {
+                // empty pairs count too, they may still be allocated while parsing
+                if query.split('&').count() > self.max_query_params {
+                    #[cfg(feature = "log")]
+                    log::warn!("Rejecting request with more than {} query params", self.max_query_params);
+                    let response = self.rejection(HttpResponse::new(
+                        String::from("1.1"),
+                        HttpStatus::BadRequest,
+                        None,
+                        None,
+                    ));
+                    return self.write_response(&mut stream, Some(&http_request), response);
+                }
+                http_request.query_params =
+                    Some(http_request.query_params_from_string(query.to_owned()));
+                (route.to_owned(), Some(query.to_owned()))
+            }
+            None => (http_request.http_headers.path.clone(), None),
+        };
+
+        if let Some(handler) = body_handler {
+            if let Some(route_params) = match_route(&handler.1, &route, self.case_insensitive_routes)
+                .filter(|route_params| !route_params.is_empty())
+            {
+                http_request.route_params = Some(route_params);
+            }
+            let response = {
+                // the head has been consumed already, the body follows on the stream
+                let reader = BufReader::new(&stream);
+                match body_reader(reader, &http_request.http_headers.extra_headers) {
+                    Ok(mut body) => {
+                        let response = (handler.2)(&http_request, &mut body);
+                        let unread = io::copy(&mut body.take(MAX_UNREAD_BODY), &mut io::sink());
+                        if let Err(_err) = unread {
+                            #[cfg(feature = "log")]
+                            log::debug!("Failed to discard unread upload body: {_err}");
+                        }
+                        response
+                    }
+                    // check_head rejects an invalid Content-Length earlier, this is only a guard
+                    Err(_) => self.rejection(HttpResponse::new(
+                        String::from("1.1"),
+                        HttpStatus::BadRequest,
+                        None,
+                        None,
+                    )),
+                }
+            };
+            let info = self.write_response(&mut stream, Some(&http_request), response)?;
+            self.counters.route(&handler.1, info.status);
+            return Ok(info);
+        }
+
+        let stream_handler = self
+            .stream_handlers
+            .iter()
+            .filter(|handler| handler.0 == http_request.http_headers.method)
+            .find_map(|handler| {
+                match_route(&handler.1, &route, self.case_insensitive_routes)
+                    .map(|route_params| (handler, route_params))
+            });
+        if let Some((handler, route_params)) = stream_handler {
+            if !route_params.is_empty() {
+                http_request.route_params = Some(route_params);
+            }
+            if handler.3 {
+                return self.handle_websocket(stream, &http_request, &handler.2);
+            }
+            (handler.2)(&http_request, stream);
+            return Ok(ResponseInfo { status: 0, bytes: 0 });
+        }
+
+        let method = &http_request.http_headers.method;
+        let host = http_request.host_and_port().map(|(host, _)| host);
+        let mut found_handler = self.find_handler(method, &route, host);
+        // retry with the trailing slash added or removed
+        if found_handler.is_none() && self.trailing_slash != TrailingSlash::Strict && route != "/" {
+            let alternate = match route.strip_suffix('/') {
+                Some(stripped) => stripped.to_owned(),
+                None => format!("{route}/"),
+            };
+            found_handler = self.find_handler(method, &alternate, host);
+            if found_handler.is_some() && self.trailing_slash == TrailingSlash::Redirect {
+                let location = match query {
+                    Some(query) => format!("{alternate}?{query}"),
+                    None => alternate,
+                };
+                #[cfg(feature = "log")]
+                log::debug!("Redirecting {route} to {location}");
+                let response = HttpResponse::redirect(HttpStatus::MovedPermanently, &location);
+                return self.write_response(&mut stream, Some(&http_request), response);
+            }
+        }
+
+        match found_handler {
+            Some((handler, route_params)) => {
+                #[cfg(feature = "log")]
+                log::debug!(
+                    "Using handler {} for {} from {}",
+                    handler.1,
+                    http_request.http_headers.path,
+                    connection
+                        .peer
+                        .map_or_else(|| String::from("unknown"), |peer| peer.to_string())
+                );
+
+                // if there are parameters, add them to the request
+                if !route_params.is_empty() {
+                    http_request.route_params = Some(route_params);
+                }
+                let info = self.handle_closure(&mut stream, http_request, raw_head, connection, &handler.2)?;
+                self.counters.route(&handler.1, info.status);
+                Ok(info)
+            }
+            None => {
+                #[cfg(feature = "log")]
+                log::warn!("Could not find handler, using default");
+
+                self.handle_closure(
+                    &mut stream,
+                    http_request,
+                    raw_head,
+                    connection,
+                    &self.default_handler,
+                )
+            }
+        }
+    }
+
+    /// Finds the handler for a route. Routes scoped to the request host are tried before the
+    /// global routes. Within each, exact routes win over wildcard routes, which only serve as
+    /// fallback before the default handler
+    fn find_handler(
+        &self,
+        method: &HttpMethod,
+        route: &str,
+        host: Option<&str>,
+    ) -> Option<(&HttpHandler, Vec<(String, String)>)> {
+        let host_handlers = self
+            .host_handlers
+            .iter()
+            .filter(|(handler_host, _)| {
+                host.is_some_and(|host| handler_host.eq_ignore_ascii_case(host))
+            })
+            .map(|(_, handler)| handler);
+        let found_handler = self
+            .match_handlers(host_handlers, method, route)
+            .or_else(|| self.match_handlers(self.handlers.iter(), method, route));
+        // HEAD is answered by the GET handler, the body is dropped when writing the response
+        if found_handler.is_none() && self.auto_head && *method == HttpMethod::HEAD {
+            return self.find_handler(&HttpMethod::GET, route, host);
+        }
+        found_handler
+    }
+
+    /// Finds the upload handler for a request head, which has to be known before the body is read
+    fn find_body_handler(&self, head: &HttpHeaders) -> Option<&BodyHandler> {
+        let route = head.path.split_once('?').map_or(head.path.as_str(), |(route, _)| route);
+        self.body_handlers.iter().find(|handler| {
+            handler.0 == head.method
+                && match_route(&handler.1, route, self.case_insensitive_routes).is_some()
+        })
+    }
+
+    /// Returns the first exact route matching, or the first matching wildcard route if there is none
+    fn match_handlers<'a>(
+        &self,
+        handlers: impl Iterator<Item = &'a HttpHandler>,
+        method: &HttpMethod,
+        route: &str,
+    ) -> Option<(&'a HttpHandler, Vec<(String, String)>)> {
+        let mut found_handler = None;
+        for handler in handlers {
+            if handler.0 != *method {
+                continue;
+            }
+            let Some(route_params) = match_route(&handler.1, route, self.case_insensitive_routes) else {
+                continue;
+            };
+            if !is_wildcard(&handler.1) {
+                return Some((handler, route_params));
+            }
+            if found_handler.is_none() {
+                found_handler = Some((handler, route_params));
+            }
+        }
+        found_handler
+    }
+
+    fn handle_closure(
+        &self,
+        stream: &mut TcpStream,
+        request: HttpRequest,
+        raw_head: Vec<u8>,
+        connection: ConnectionInfo,
+        exec: &Arc<RouteFn>,
+    ) -> std::io::Result<ResponseInfo> {
+        let Some(timeout) = self.handler_timeout else {
+            return self.write_response(stream, Some(&request), exec(&request, &raw_head));
+        };
+        let slot = HandlerSlot::acquire(&self.handler_threads);
+        if slot.outstanding > self.max_handler_threads {
+            #[cfg(feature = "log")]
+            log::warn!(
+                "Handler thread limit reached, rejecting {}",
+                request.http_headers.path
+            );
+            let response = self.rejection(HttpResponse::new(
+                String::from("1.1"),
+                HttpStatus::ServiceUnavailable,
+                None,
+                None,
+            ));
+            return self.write_response(stream, Some(&request), response);
+        }
+        // the handler runs on its own thread so the client can be answered once the timeout passed
+        let request = Arc::new(request);
+        let (sender, receiver) = mpsc::channel();
+        let (handler_exec, handler_request) = (Arc::clone(exec), Arc::clone(&request));
+        thread::Builder::new()
+            .name(String::from("http-handler"))
+            .spawn(move || {
+                // released once the handler returned or panicked
+                let _slot = slot;
+                // the receiver is gone once the handler timed out
+                let response =
+                    with_connection(connection, || handler_exec(&handler_request, &raw_head));
+                let _ = sender.send(response);
+            })?;
+        let response = match receiver.recv_timeout(timeout) {
+            Ok(response) => response,
+            Err(RecvTimeoutError::Timeout) => {
+                #[cfg(feature = "log")]
+                log::warn!(
+                    "Handler for {} did not finish within {timeout:?}",
+                    request.http_headers.path
+                );
+                self.rejection(HttpResponse::new(
+                    String::from("1.1"),
+                    HttpStatus::GatewayTimeout,
+                    None,
+                    None,
+                ))
+            }
+            // the handler panicked before sending a response
+            Err(RecvTimeoutError::Disconnected) => {
+                #[cfg(feature = "log")]
+                log::error!("Handler for {} panicked", request.http_headers.path);
+                self.rejection(HttpResponse::new(
+                    String::from("1.1"),
+                    HttpStatus::InternalServerError,
+                    None,
+                    None,
+                ))
+            }
+        };
+        self.write_response(stream, Some(&request), response)
+    }
+
+    /// Performs the websocket handshake and hands the connection to the handler if it succeeds
+    fn handle_websocket(
+        &self,
+        mut stream: TcpStream,
+        request: &HttpRequest,
+        exec: &StreamHandlerFn,
+    ) -> std::io::Result<ResponseInfo> {
+        let response = match websocket::handshake(request) {
+            Ok(response) => response,
+            Err(response) => {
+                #[cfg(feature = "log")]
+                log::debug!("Rejecting websocket handshake for {}", request.http_headers.path);
+                let response = self.rejection(response);
+                return self.write_response(&mut stream, Some(request), response);
+            }
+        };
+        let info = self.write_response(&mut stream, None, response)?;
+        exec(request, stream);
+        Ok(info)
+    }
+
+    fn write_response(
+        &self,
+        stream: &mut TcpStream,
+        request: Option<&HttpRequest>,
+        mut response: HttpResponse,
+    ) -> std::io::Result<ResponseInfo> {
+        if let Some(request) = request {
+            if self.etag && response.header("ETag").is_none() {
+                response = response.with_etag();
+            }
+            apply_conditional(request, &mut response);
+        }
+        let stream_range = request.and_then(|request| apply_range(request, &mut response));
+        if self.no_cache && response.header("Cache-Control").is_none() {
+            response.set_header("Cache-Control", "no-store");
+        }
+        if let Some(server) = self.server_header.as_ref().filter(|_| response.header("Server").is_none()) {
+            response.set_header("Server", server);
+        }
+        // HTTP/1.0 clients are answered in their version, they might not handle a 1.1 response
+        if request.is_some_and(|request| http_version(&request.http_headers.protocol) == "1.0") {
+            response.protocol = String::from("1.0");
+        }
+        // every connection serves a single request, so even keep-alive clients are told it closes.
+        // Only upgrades keep their own Connection header
+        if response.status.code() != 101 {
+            response.set_header("Connection", "close");
+        }
+        let closing = response
+            .header("Connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"));
+        if self.date_header && response.header("Date").is_none() {
+            response.set_header("Date", &DateTime::from_system_time(SystemTime::now()).to_http_date());
+        }
+        if let (Some(cors), Some(origin)) = (
+            &self.cors,
+            request.and_then(|request| request.header("Origin")),
+        ) {
+            cors.apply(origin, &mut response);
+        }
+        let mut info = ResponseInfo {
+            status: response.status.code(),
+            bytes: 0,
+        };
+        remove_forbidden_body(&mut response);
+        remove_conflicting_framing(&mut response);
+        // streams of unknown length are framed with chunked encoding, HTTP/1.0 clients do not
+        // support it and read until the connection is closed instead
+        let chunked = matches!(response.data, Some(HttpData::Stream(_)))
+            && response.header("Content-Length").is_none()
+            && request.map_or(true, |request| {
+                http_version(&request.http_headers.protocol) != "1.0"
+            });
+        if chunked {
+            response.set_header("Transfer-Encoding", "chunked");
+        }
+        // headers are built with the body still attached so HEAD carries the same Content-Length as GET
+        let head = response.to_headers().join("\r\n");
+        let head = match &self.raw_response {
+            // a panicking hook leaves the head as it is, as rejections are written outside of the
+            // handler's catch_unwind
+            Some(exec) => match panic::catch_unwind(AssertUnwindSafe(|| exec(head.as_bytes()))) {
+                Ok(raw) => raw,
+                Err(_) => {
+                    #[cfg(feature = "log")]
+                    log::error!("Raw response hook panicked, sending the head unchanged");
+                    head.into_bytes()
+                }
+            },
+            None => head.into_bytes(),
+        };
+        stream.write_all(&head)?;
+        if request.is_some_and(|request| request.http_headers.method == HttpMethod::HEAD) {
+            stream.flush()?;
+            self.counters.response(info.status, head.len() as u64);
+            if closing {
+                shutdown_write(stream);
+            }
+            return Ok(info);
+        }
+        info.bytes = match response.data {
+            Some(data) => match data {
+                HttpData::Bytes(vec) => {
+                    stream.write_all(&vec)?;
+                    vec.len() as u64
                 }
+                HttpData::Stream(mut read) => match stream_range {
+                    Some((skip, length)) => {
+                        io::copy(&mut (&mut read.0).take(skip), &mut io::sink())?;
+                        io::copy(&mut read.0.take(length), stream)?
+                    }
+                    None if chunked => {
+                        let mut writer = ChunkedWriter::new(&mut *stream);
+                        let length = io::copy(&mut read.0, &mut writer)?;
+                        writer.finish()?;
+                        length
+                    }
+                    None => io::copy(&mut read.0, stream)?,
+                },
+            },
+            None => 0,
+        };
+        stream.flush()?;
+        self.counters.response(info.status, head.len() as u64 + info.bytes);
+        if closing {
+            shutdown_write(stream);
+        }
+        Ok(info)
+    }
+}
+
+/// Registers handlers scoped to a single host, created by [`HttpServer::host`]
+pub struct HostScope<'a> {
+    server: &'a mut HttpServer,
+    host: String,
+}
+
+impl HostScope<'_> {
+    /// Adds a get method handler for this host
+    pub fn get(
+        &mut self,
+        path: String,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.add(HttpMethod::GET, path, Box::new(exec))
+    }
+
+    /// Adds a post method handler for this host
+    pub fn post(
+        &mut self,
+        path: String,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.add(HttpMethod::POST, path, Box::new(exec))
+    }
+
+    /// Adds a put method handler for this host
+    pub fn put(
+        &mut self,
+        path: String,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.add(HttpMethod::PUT, path, Box::new(exec))
+    }
+
+    /// Adds a delete method handler for this host
+    pub fn delete(
+        &mut self,
+        path: String,
+        exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.add(HttpMethod::DELETE, path, Box::new(exec))
+    }
+
+    fn add(&mut self, method: HttpMethod, path: String, exec: HttpHandlerFn) -> &mut Self {
+        #[cfg(feature = "log")]
+        log::debug!("Adding {method:?} {path} for host {}", self.host);
+        self.server
+            .host_handlers
+            .push((self.host.clone(), (method, path, Arc::new(ignore_head(exec)))));
+        self
+    }
+}
+
+/// Status code and body size of a written response
+#[derive(Debug, Clone, Copy)]
+struct ResponseInfo {
+    status: u16,
+    bytes: u64,
+}
+
+/// Counts a handler thread spawned for `handler_timeout` as outstanding until dropped
+struct HandlerSlot {
+    threads: Arc<AtomicUsize>,
+    // threads outstanding including this one
+    outstanding: usize,
+}
+
+impl HandlerSlot {
+    fn acquire(threads: &Arc<AtomicUsize>) -> Self {
+        let outstanding = threads.fetch_add(1, Ordering::SeqCst) + 1;
+        HandlerSlot {
+            threads: Arc::clone(threads),
+            outstanding,
+        }
+    }
+}
+
+impl Drop for HandlerSlot {
+    fn drop(&mut self) {
+        self.threads.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Wraps a handler for routes registered without `capture_raw`, which do not use the request head
+fn ignore_head(exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static) -> RouteFn {
+    Box::new(move |request: &HttpRequest, _: &[u8]| exec(request))
+}
+
+/// Wraps a handler so its responses get the given Content-Type unless the handler sets one
+fn with_default_content_type(
+    exec: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    content_type: &'static str,
+) -> impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static {
+    move |request: &HttpRequest| {
+        let mut response = exec(request);
+        if response.header("Content-Type").is_none() {
+            response.set_header("Content-Type", content_type);
+        }
+        response
+    }
+}
+
+/// Drops the body of 1xx, 204 and 304 responses, which must not carry one. 1xx and 204 responses
+/// also lose their framing headers, a stray body would otherwise desync keep-alive clients
+fn remove_forbidden_body(response: &mut HttpResponse) {
+    let code = response.status.code();
+    if !matches!(code, 100..=199 | 204 | 304) {
+        return;
+    }
+    if response.data.take().is_some() {
+        #[cfg(feature = "log")]
+        log::warn!("Dropping body of {code} response, which must not have one");
+    }
+    if code != 304 {
+        if let Some(headers) = &mut response.extra_headers {
+            headers.retain(|(key, _)| {
+                !key.eq_ignore_ascii_case("Content-Length") && !key.eq_ignore_ascii_case("Transfer-Encoding")
+            });
+        }
+    }
+}
+
+/// Drops Content-Length and Transfer-Encoding headers set by the handler on byte bodies, as the
+/// Content-Length computed from the body is always sent and duplicate framing headers break clients
+fn remove_conflicting_framing(response: &mut HttpResponse) {
+    let (Some(HttpData::Bytes(_bytes)), Some(headers)) = (&response.data, &mut response.extra_headers) else {
+        return;
+    };
+    headers.retain(|(key, _value)| {
+        let is_length = key.eq_ignore_ascii_case("Content-Length");
+        if !is_length && !key.eq_ignore_ascii_case("Transfer-Encoding") {
+            return true;
+        }
+        #[cfg(feature = "log")]
+        if !is_length || _value.trim() != _bytes.len().to_string() {
+            log::warn!(
+                "Dropping {key}: {_value} header conflicting with Content-Length: {}",
+                _bytes.len()
+            );
+        }
+        false
+    });
+}
+
+/// Signals the end of the response to the client, so it sees the close even while unread request
+/// data is still pending on the connection
+fn shutdown_write(stream: &TcpStream) {
+    if let Err(_err) = stream.shutdown(Shutdown::Write) {
+        #[cfg(feature = "log")]
+        log::debug!("Failed to shut down connection: {_err}");
+    }
+}
+
+/// Returns the HTTP version of a request, e.g. `1.1`, whether its protocol carries the `HTTP/`
+/// prefix or not
+fn http_version(protocol: &str) -> &str {
+    protocol.trim_start_matches("HTTP/")
+}
+
+/// Returns the name a route param is stored under, `:id` as `id` and `:path*` as `path`
+fn param_name(section: &str) -> String {
+    match section.trim_start_matches(':').trim_end_matches('*') {
+        "" => String::from("*"),
+        name => name.to_owned(),
+    }
+}
+
+/// Checks whether an io error means the client closed the connection, which is expected and not a server failure
+fn is_disconnect(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+    )
+}
+
+/// Checks that a request path starts with `/` (or is `*` for OPTIONS) and contains no control characters
+fn is_valid_path(path: &str, method: &HttpMethod) -> bool {
+    if path.chars().any(|c| c.is_ascii_control()) {
+        return false;
+    }
+    path.starts_with('/') || path == "*" && *method == HttpMethod::OPTIONS
+}
+
+/// Checks whether a defined route ends in a wildcard section (`*` or `:name*`)
+fn is_wildcard(defined: &str) -> bool {
+    defined
+        .rsplit('/')
+        .next()
+        .is_some_and(|section| section.ends_with('*'))
+}
+
+/// Matches a received route against a defined route, returning the extracted route params on success.
+/// `:name` matches a single section, `*` and `:name*` match the rest of the path including slashes,
+/// which is empty for `/files/` and `/files` against `/files/:path*`.
+/// Route params are always extracted as received, case_insensitive only affects literal sections.
+/// Params are named without the colon and star, an unnamed `*` is stored as `*`
+fn match_route(
+    defined: &str,
+    received: &str,
+    case_insensitive: bool,
+) -> Option<Vec<(String, String)>> {
+    let defined_parts = defined.split('/').collect::<Vec<&str>>();
+    let received_parts = received.split('/').collect::<Vec<&str>>();
+    let mut route_params = Vec::new();
+
+    for (idx, defined_section) in defined_parts.iter().enumerate() {
+        // treat the rest of the path as single param
+        if defined_section.ends_with('*') {
+            let rest = received_parts.get(idx..).unwrap_or_default().join("/");
+            route_params.push((param_name(defined_section), rest));
+            return Some(route_params);
+        }
+        let received_section = received_parts.get(idx)?;
+        if defined_section.starts_with(':') {
+            route_params.push((param_name(defined_section), received_section.to_string()));
+        } else {
+            let matches = if case_insensitive {
+                received_section.eq_ignore_ascii_case(defined_section)
+            } else {
+                received_section == defined_section
+            };
+            if !matches {
+                return None;
             }
-        },
-        None => Ok(()),
+        }
     }
+
+    (defined_parts.len() == received_parts.len()).then_some(route_params)
 }
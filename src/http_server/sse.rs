@@ -0,0 +1,88 @@
+use std::sync::mpsc::{self, SendError, Sender};
+
+use http_base::http::http_structs::{HttpData, HttpResponse, HttpStatus, HttpStream};
+
+use super::response::{ChunkIterReader, HttpResponseExt};
+
+/// Creates a server-sent events stream. The response is returned from the handler and keeps the
+/// connection open, every event sent through the sender is written to the client as it arrives.
+///
+/// The stream ends when every sender is dropped. Once the client disconnects, writing the next
+/// event fails, the response is dropped and [`EventSender::send`] returns an error, so producers
+/// should stop when sending fails. The disconnect itself is only logged at debug level.
+/// Producers that rarely send can call [`EventSender::keep_alive`] to notice it earlier.
+/// Example:
+/// ```rust
+/// use std::{thread, time::Duration};
+/// use http_serv::{HttpRequest, http_server::sse};
+///
+/// let handler = |_: &HttpRequest| {
+///     let (sender, response) = sse::channel();
+///     thread::spawn(move || {
+///         for tick in 0.. {
+///             if sender.send(&format!("tick {tick}")).is_err() {
+///                 // the client disconnected
+///                 break;
+///             }
+///             thread::sleep(Duration::from_secs(1));
+///         }
+///     });
+///     response
+/// };
+/// ```
+pub fn channel() -> (EventSender, HttpResponse) {
+    let (sender, receiver) = mpsc::channel();
+    // blocks until the next event arrives, the body ends once every sender is gone
+    let reader = ChunkIterReader::new(receiver.into_iter());
+    let mut response = HttpResponse::new(
+        String::from("1.1"),
+        HttpStatus::Ok,
+        None,
+        Some(HttpData::Stream(HttpStream(Box::new(reader)))),
+    );
+    response.set_header("Content-Type", "text/event-stream");
+    response.set_header("Cache-Control", "no-cache");
+    (EventSender(sender), response)
+}
+
+/// Sending half of a server-sent events stream, see [`channel`]
+#[derive(Debug, Clone)]
+pub struct EventSender(Sender<Vec<u8>>);
+
+impl EventSender {
+    /// Sends an unnamed event. Multi line data is split into one `data:` field per line
+    pub fn send(&self, data: &str) -> Result<(), SendError<()>> {
+        self.send_frame(format_event(None, data))
+    }
+
+    /// Sends an event with an `event:` name, which browsers dispatch to listeners of that name
+    pub fn send_named(&self, event: &str, data: &str) -> Result<(), SendError<()>> {
+        self.send_frame(format_event(Some(event), data))
+    }
+
+    /// Sends a comment line that clients ignore. Useful to keep idle connections open through
+    /// proxies and to notice a disconnected client while there are no events to send
+    pub fn keep_alive(&self) -> Result<(), SendError<()>> {
+        self.send_frame(String::from(":\n\n"))
+    }
+
+    fn send_frame(&self, frame: String) -> Result<(), SendError<()>> {
+        self.0.send(frame.into_bytes()).map_err(|_| SendError(()))
+    }
+}
+
+/// Formats a single event frame terminated by a blank line
+fn format_event(event: Option<&str>, data: &str) -> String {
+    let mut frame = String::new();
+    if let Some(event) = event {
+        frame.push_str(&format!("event: {}\n", event.replace(['\r', '\n'], "")));
+    }
+    for line in data.lines() {
+        frame.push_str(&format!("data: {line}\n"));
+    }
+    if data.is_empty() {
+        frame.push_str("data: \n");
+    }
+    frame.push('\n');
+    frame
+}
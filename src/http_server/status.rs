@@ -0,0 +1,115 @@
+use http_base::http::http_structs::HttpStatus;
+
+/// Numeric conversions for HttpStatus
+pub trait HttpStatusExt: Sized {
+    /// Converts a numeric status code into its HttpStatus, returns None for unknown codes
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpStatus, HttpStatusExt};
+    ///
+    /// assert_eq!(HttpStatus::from_u16(404), Some(HttpStatus::NotFound));
+    /// assert_eq!(HttpStatus::from_u16(999), None);
+    /// ```
+    fn from_u16(code: u16) -> Option<Self>;
+
+    /// Returns the numeric status code
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpStatus, HttpStatusExt};
+    ///
+    /// assert_eq!(HttpStatus::NotImplemented.code(), 501);
+    /// ```
+    fn code(&self) -> u16;
+
+    /// Returns the canonical reason phrase of the status, e.g. "Not Found"
+    /// Example:
+    /// ```rust
+    /// use http_serv::{HttpStatus, HttpStatusExt};
+    ///
+    /// assert_eq!(HttpStatus::NotImplemented.reason_phrase(), "Not Implemented");
+    /// ```
+    fn reason_phrase(&self) -> &'static str;
+}
+
+impl HttpStatusExt for HttpStatus {
+    fn from_u16(code: u16) -> Option<Self> {
+        HttpStatus::try_from(code as u64).ok()
+    }
+
+    fn code(&self) -> u16 {
+        *self as u16
+    }
+
+    fn reason_phrase(&self) -> &'static str {
+        reason_phrase(self.code())
+    }
+}
+
+/// Maps a numeric status code to its canonical reason phrase, unknown codes yield an empty phrase
+pub(crate) fn reason_phrase(code: u16) -> &'static str {
+    match code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        102 => "Processing",
+        103 => "Early Hints",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        203 => "Non-Authoritative Information",
+        204 => "No Content",
+        205 => "Reset Content",
+        206 => "Partial Content",
+        207 => "Multi-Status",
+        208 => "Already Reported",
+        226 => "IM Used",
+        300 => "Multiple Choices",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        305 => "Use Proxy",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        402 => "Payment Required",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        407 => "Proxy Authentication Required",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Content Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        417 => "Expectation Failed",
+        418 => "I'm a teapot",
+        421 => "Misdirected Request",
+        422 => "Unprocessable Content",
+        423 => "Locked",
+        424 => "Failed Dependency",
+        425 => "Too Early",
+        426 => "Upgrade Required",
+        428 => "Precondition Required",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        451 => "Unavailable For Legal Reasons",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        506 => "Variant Also Negotiates",
+        507 => "Insufficient Storage",
+        508 => "Loop Detected",
+        510 => "Not Extended",
+        511 => "Network Authentication Required",
+        _ => "",
+    }
+}
@@ -0,0 +1,155 @@
+use http_base::http::http_structs::{HttpMethod, HttpRequest, HttpResponse, HttpStatus};
+
+use super::{request::HttpRequestExt, response::HttpResponseExt};
+
+/// GUID appended to the client key before hashing, defined in RFC 6455
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Computes the Sec-WebSocket-Accept value for a Sec-WebSocket-Key
+/// Example:
+/// ```rust
+/// use http_serv::http_server::websocket::accept_key;
+///
+/// assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+/// ```
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{WEBSOCKET_GUID}", key.trim()).as_bytes()))
+}
+
+/// Checks whether a request asks for a websocket upgrade
+pub fn is_upgrade(request: &HttpRequest) -> bool {
+    request.http_headers.method == HttpMethod::GET
+        && request
+            .header("Upgrade")
+            .is_some_and(|upgrade| has_token(upgrade, "websocket"))
+        && request
+            .header("Connection")
+            .is_some_and(|connection| has_token(connection, "upgrade"))
+}
+
+/// Builds the handshake response for a websocket upgrade request. Returns the
+/// `101 Switching Protocols` response if the handshake is valid, otherwise the `400 Bad Request`
+/// response to send instead
+/// Example:
+/// ```rust
+/// use http_serv::http_server::websocket::handshake;
+/// use http_serv::{HttpMethod, HttpResponseExt, HttpStatus, example_request};
+///
+/// let request = example_request(
+///     HttpMethod::GET,
+///     "/chat",
+///     &[
+///         ("Upgrade", "websocket"),
+///         ("Connection", "Upgrade"),
+///         ("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="),
+///         ("Sec-WebSocket-Version", "13"),
+///     ],
+/// );
+/// let response = handshake(&request).unwrap();
+/// assert_eq!(response.status, HttpStatus::SwitchingProtocols);
+/// assert_eq!(response.header("Sec-WebSocket-Accept"), Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+/// ```
+pub fn handshake(request: &HttpRequest) -> Result<HttpResponse, HttpResponse> {
+    let mut response = HttpResponse::new(String::from("1.1"), HttpStatus::BadRequest, None, None);
+    if !is_upgrade(request) {
+        response.set_header("Upgrade", "websocket");
+        return Err(response);
+    }
+    if request.header("Sec-WebSocket-Version").map(str::trim) != Some("13") {
+        response.set_header("Sec-WebSocket-Version", "13");
+        return Err(response);
+    }
+    let Some(key) = request
+        .header("Sec-WebSocket-Key")
+        .filter(|key| !key.trim().is_empty())
+    else {
+        return Err(response);
+    };
+    response.status = HttpStatus::SwitchingProtocols;
+    response.set_header("Upgrade", "websocket");
+    response.set_header("Connection", "Upgrade");
+    response.set_header("Sec-WebSocket-Accept", &accept_key(key));
+    Ok(response)
+}
+
+/// Checks whether a comma separated header value contains a token, ignoring case
+fn has_token(value: &str, token: &str) -> bool {
+    value
+        .split(',')
+        .any(|part| part.trim().eq_ignore_ascii_case(token))
+}
+
+/// SHA-1 digest as required by the websocket handshake, not meant for anything security related
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut words = [0u32; 80];
+        for (idx, word) in block.chunks_exact(4).enumerate() {
+            words[idx] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for idx in 16..80 {
+            words[idx] = (words[idx - 3] ^ words[idx - 8] ^ words[idx - 14] ^ words[idx - 16])
+                .rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (idx, word) in words.iter().enumerate() {
+            let (f, k) = match idx {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, value) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+/// Standard base64 encoding with padding
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(ALPHABET[(triple >> (18 - idx * 6) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -3,3 +3,9 @@ pub mod http_server;
 pub use http_base::http::http_structs::{
     HttpData, HttpHeaders, HttpMethod, HttpRequest, HttpResponse, HttpStatus,
 };
+pub use http_server::{
+    head::ParseError, method::HttpMethodExt, request::HttpRequestExt, response::HttpResponseExt,
+    status::HttpStatusExt,
+};
+#[doc(hidden)]
+pub use http_server::request::example_request;
@@ -0,0 +1,94 @@
+// each test binary only uses some of the helpers
+#![allow(dead_code)]
+
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use http_serv::http_server::server::HttpServer;
+
+/// Creates a server bound to a free port on localhost
+#[cfg(not(feature = "threading"))]
+pub fn server() -> HttpServer {
+    HttpServer::new("127.0.0.1".to_string(), "0".to_string(), Vec::new(), None).unwrap()
+}
+
+/// Creates a server bound to a free port on localhost with two worker threads
+#[cfg(feature = "threading")]
+pub fn server() -> HttpServer {
+    server_with_threads(2)
+}
+
+/// Creates a server bound to a free port on localhost with the given number of worker threads
+#[cfg(feature = "threading")]
+pub fn server_with_threads(threads: u32) -> HttpServer {
+    #[cfg(feature = "num_cpus")]
+    let threads = Some(threads);
+    HttpServer::new("127.0.0.1".to_string(), "0".to_string(), threads, Vec::new(), None).unwrap()
+}
+
+/// A server running its loop on a separate thread, shut down when dropped
+pub struct Running {
+    pub server: Arc<HttpServer>,
+    pub addr: SocketAddr,
+    handle: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Running {
+    pub fn start(server: HttpServer) -> Self {
+        let server = Arc::new(server);
+        let addr = server.local_addr().unwrap();
+        let runner = Arc::clone(&server);
+        let handle = thread::spawn(move || runner.run_loop());
+        Self {
+            server,
+            addr,
+            handle: Some(handle),
+        }
+    }
+
+    /// Opens a connection and writes raw to it
+    pub fn connect(&self, raw: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(self.addr).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        stream
+    }
+
+    /// Sends raw on a new connection and returns everything the server writes until it closes it
+    pub fn send(&self, raw: &str) -> String {
+        read_response(self.connect(raw))
+    }
+
+    /// Sends a request without body for path with the given method
+    pub fn request(&self, method: &str, path: &str) -> String {
+        self.send(&format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\n\r\n"))
+    }
+
+    pub fn get(&self, path: &str) -> String {
+        self.request("GET", path)
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.server.shutdown();
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        let result = handle.join();
+        // a failed assertion is already unwinding, panicking again would abort the test binary
+        if !thread::panicking() {
+            result.unwrap().unwrap();
+        }
+    }
+}
+
+/// Reads the response until the server closes the connection
+pub fn read_response(mut stream: TcpStream) -> String {
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
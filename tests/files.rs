@@ -0,0 +1,49 @@
+mod common;
+
+use std::fs;
+
+use common::Running;
+
+#[test]
+fn serve_dir_with_index() {
+    let dir = std::env::temp_dir().join(format!("http_serv_docs_{}", std::process::id()));
+    fs::create_dir_all(dir.join("guide")).unwrap();
+    fs::write(dir.join("guide").join("start.html"), "<h1>Guide</h1>").unwrap();
+    let mut server = common::server();
+    server.serve_dir_with_index("/docs", &dir, "start.html");
+    let running = Running::start(server);
+
+    assert!(running.get("/docs/guide/").ends_with("<h1>Guide</h1>"));
+    let full = running.get("/docs/guide/start.html");
+    assert!(full.ends_with("<h1>Guide</h1>"));
+    assert!(full.contains("Accept-Ranges: bytes\r\n"));
+    let partial = running.send(
+        "GET /docs/guide/start.html HTTP/1.1\r\nHost: localhost\r\nRange: bytes=4-8\r\n\r\n",
+    );
+    assert!(partial.starts_with("HTTP/1.1 206"));
+    assert!(partial.ends_with("\r\n\r\nGuide"));
+    assert!(running.get("/docs/guide").starts_with("HTTP/1.1 301"));
+    assert!(running.get("/docs/guide/start.html/").starts_with("HTTP/1.1 404"));
+    assert!(running.get("/docs/").starts_with("HTTP/1.1 404"));
+    assert!(running.get("/docs/%2e%2e/guide/start.html").starts_with("HTTP/1.1 404"));
+    drop(running);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn serve_dir_with_listing() {
+    let dir = std::env::temp_dir().join(format!("http_serv_share_{}", std::process::id()));
+    fs::create_dir_all(dir.join("photos")).unwrap();
+    fs::write(dir.join("notes.txt"), "abc").unwrap();
+    fs::write(dir.join("<b>.txt"), "").unwrap();
+    let mut server = common::server();
+    server.serve_dir_with_listing("/share", &dir, "index.html");
+    let running = Running::start(server);
+
+    let listing = running.get("/share/");
+    assert!(listing.contains("<a href=\"photos/\">photos/</a>"));
+    assert!(listing.contains("<a href=\"notes.txt\">notes.txt</a> 3 bytes"));
+    assert!(listing.contains("<a href=\"%3Cb%3E.txt\">&lt;b&gt;.txt</a>"));
+    drop(running);
+    fs::remove_dir_all(dir).unwrap();
+}
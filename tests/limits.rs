@@ -0,0 +1,142 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use common::Running;
+use http_serv::{HttpRequest, HttpResponse, HttpStatus};
+
+#[test]
+fn max_headers_and_malformed_heads() {
+    let mut server = common::server();
+    server.max_headers(2);
+    server.get("/".to_owned(), |_: &HttpRequest| {
+        HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, None)
+    });
+    let running = Running::start(server);
+
+    assert!(running.send("GET / HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n").starts_with("HTTP/1.1 200"));
+    assert!(running.send("GET / HTTP/1.1\r\nHost: localhost\r\nA: 1\r\nB: 2\r\n\r\n").starts_with("HTTP/1.1 431"));
+    assert!(running.send("not a request\r\n\r\n").starts_with("HTTP/1.1 400"));
+    // chunked has to be the final transfer coding, it is the only one supported
+    assert!(running.send("GET / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: gzip, chunked\r\n\r\n").starts_with("HTTP/1.1 501"));
+    assert!(running.send("GET / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked, gzip\r\n\r\n").starts_with("HTTP/1.1 400"));
+}
+
+#[test]
+fn oversized_body_is_discarded_after_413() {
+    let mut server = common::server();
+    server.max_body_size(1024);
+    server.post("/".to_owned(), |_: &HttpRequest| {
+        HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, None)
+    });
+    let running = Running::start(server);
+
+    // the body is sent anyway, it is discarded so the rejection is not lost to a connection reset
+    let body = "x".repeat(64 * 1024);
+    let upload = format!(
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    assert!(running.send(&upload).starts_with("HTTP/1.1 413"));
+}
+
+#[test]
+fn max_accept_rate_serves_every_connection() {
+    let mut server = common::server();
+    // the spacing of accepts is covered by the unit tests of the throttle
+    server.max_accept_rate(100);
+    server.get("/".to_owned(), |_: &HttpRequest| {
+        HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, None)
+    });
+    let running = Running::start(server);
+
+    let streams: Vec<_> = (0..4)
+        .map(|_| running.connect("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"))
+        .collect();
+    for stream in streams {
+        assert!(common::read_response(stream).starts_with("HTTP/1.1 200"));
+    }
+}
+
+#[test]
+fn max_handler_threads_rejects_with_503() {
+    let mut server = common::server();
+    server.handler_timeout(Duration::from_millis(100)).max_handler_threads(1);
+    server.get("/slow".to_owned(), |_: &HttpRequest| {
+        thread::sleep(Duration::from_secs(1));
+        HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, None)
+    });
+    let running = Running::start(server);
+    let get = || running.send("GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+
+    assert!(get().starts_with("HTTP/1.1 504"));
+    // the timed out handler is still running and takes up the only thread
+    assert!(get().starts_with("HTTP/1.1 503"));
+}
+
+#[cfg(feature = "threading")]
+#[test]
+fn max_connections_rejects_with_503() {
+    use std::sync::{mpsc, Mutex};
+
+    let mut server = common::server();
+    server.max_connections(1);
+    // the handler holds its connection until it is released
+    let (started, wait_started) = mpsc::channel();
+    let (release, released) = mpsc::channel::<()>();
+    let (started, released) = (Mutex::new(started), Mutex::new(released));
+    server.get("/slow".to_owned(), move |_: &HttpRequest| {
+        started.lock().unwrap().send(()).unwrap();
+        released.lock().unwrap().recv().unwrap();
+        HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, None)
+    });
+    let running = Running::start(server);
+    let request = "GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    let held = running.connect(request);
+    wait_started.recv().unwrap();
+
+    // the limit is reached, the next connection is rejected without reaching a handler
+    assert!(running.send(request).starts_with("HTTP/1.1 503"));
+    // the unread request is discarded, so the rejection is not lost to a connection reset
+    let body = "x".repeat(64 * 1024);
+    let upload = format!(
+        "POST /slow HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    assert!(running.send(&upload).starts_with("HTTP/1.1 503"));
+    release.send(()).unwrap();
+    assert!(common::read_response(held).starts_with("HTTP/1.1 200"));
+}
+
+#[cfg(feature = "threading")]
+#[test]
+fn full_queue_rejects_with_503() {
+    use std::sync::{mpsc, Mutex};
+
+    let mut server = common::server_with_threads(1);
+    server.queue_size(1);
+    // the handler holds the only worker until it is released
+    let (started, wait_started) = mpsc::channel();
+    let (release, released) = mpsc::channel::<()>();
+    let (started, released) = (Mutex::new(started), Mutex::new(released));
+    server.get("/slow".to_owned(), move |_: &HttpRequest| {
+        let _ = started.lock().unwrap().send(());
+        released.lock().unwrap().recv().unwrap();
+        HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, None)
+    });
+    let running = Running::start(server);
+    let request = "GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    let held = running.connect(request);
+    wait_started.recv().unwrap();
+    let queued = running.connect(request);
+
+    // the worker is busy and the queue is full, the accept loop answers without waiting for them
+    let rejected = running.connect(request);
+    rejected.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert!(common::read_response(rejected).starts_with("HTTP/1.1 503"));
+
+    release.send(()).unwrap();
+    release.send(()).unwrap();
+    assert!(common::read_response(held).starts_with("HTTP/1.1 200"));
+    assert!(common::read_response(queued).starts_with("HTTP/1.1 200"));
+}
@@ -0,0 +1,365 @@
+mod common;
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    thread,
+};
+
+use common::Running;
+use http_serv::{
+    http_server::cors::Cors, HttpData, HttpMethod, HttpRequest, HttpRequestExt, HttpResponse,
+    HttpResponseExt, HttpStatus,
+};
+
+#[test]
+fn named_wildcard_holds_rest_of_path() {
+    let mut server = common::server();
+    server.get("/files/:path*".to_owned(), |request: &HttpRequest| {
+        let path = request
+            .route_params
+            .iter()
+            .flatten()
+            .find(|(key, _)| key == "path")
+            .map(|(_, value)| value.clone());
+        HttpResponse {
+            data: Some(HttpData::Bytes(format!("{path:?}").into_bytes())),
+            ..Default::default()
+        }
+    });
+    server.get("/deep/:dir/:rest*".to_owned(), |_: &HttpRequest| HttpResponse::default());
+    let running = Running::start(server);
+
+    assert!(running.get("/files/a/b/c.txt").ends_with("Some(\"a/b/c.txt\")"));
+    assert!(running.get("/files/").ends_with("Some(\"\")"));
+    assert!(running.get("/files").ends_with("Some(\"\")"));
+    // without a default handler unmatched requests are answered with 501
+    assert!(running.get("/deep").starts_with("HTTP/1.1 501"));
+}
+
+#[test]
+fn get_json_defaults_content_type() {
+    let mut server = common::server();
+    server.get_json("/users".to_owned(), |_: &HttpRequest| {
+        HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, Some(HttpData::Bytes(b"[]".to_vec())))
+    });
+    server.get_json("/users.csv".to_owned(), |_: &HttpRequest| {
+        let mut resp = HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, Some(HttpData::Bytes(b"id\n".to_vec())));
+        resp.set_header("Content-Type", "text/csv");
+        resp
+    });
+    let running = Running::start(server);
+
+    assert!(running.get("/users").contains("Content-Type: application/json\r\n"));
+    // a Content-Type set by the handler wins over the default
+    let response = running.get("/users.csv");
+    assert!(response.contains("Content-Type: text/csv\r\n"));
+    assert!(!response.contains("application/json"));
+}
+
+#[test]
+fn post_typed_checks_content_type() {
+    let mut server = common::server();
+    server.post_typed("/users".to_owned(), "application/json", |request: &HttpRequest| {
+        let body = request.data.as_deref().unwrap_or_default();
+        let status = match body.starts_with(b"{") && body.ends_with(b"}") {
+            true => HttpStatus::Created,
+            false => HttpStatus::BadRequest,
+        };
+        HttpResponse::new(String::from("1.1"), status, None, None)
+    });
+    let running = Running::start(server);
+    let post = |content_type: &str, body: &str| {
+        running.send(&format!(
+            "POST /users HTTP/1.1\r\nHost: localhost\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        ))
+    };
+
+    assert!(post("application/json", r#"{"name":"ada"}"#).starts_with("HTTP/1.1 201"));
+    assert!(post("application/json; charset=utf-8", "{}").starts_with("HTTP/1.1 201"));
+    assert!(post("application/xml", "<name>ada</name>").starts_with("HTTP/1.1 415"));
+    // the handler still validates the body itself
+    assert!(post("application/json", "name=ada").starts_with("HTTP/1.1 400"));
+}
+
+#[test]
+fn route_serves_every_method() {
+    let mut server = common::server();
+    server.route(&[HttpMethod::GET, HttpMethod::POST], "/users".to_owned(), |request: &HttpRequest| {
+        HttpResponse {
+            data: Some(HttpData::Bytes(format!("{:?}", request.http_headers.method).into_bytes())),
+            ..Default::default()
+        }
+    });
+    let running = Running::start(server);
+    let send = |method: &str| {
+        running.send(&format!("{method} /users HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n"))
+    };
+
+    assert!(send("GET").ends_with("GET"));
+    assert!(send("POST").ends_with("POST"));
+}
+
+#[test]
+fn case_insensitive_routes() {
+    let fetch = |case_insensitive: bool, path: &str| {
+        let mut server = common::server();
+        server.case_insensitive_routes(case_insensitive);
+        server.get("/users".to_owned(), |_: &HttpRequest| {
+            HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, None)
+        });
+        server.default(|_: &HttpRequest| HttpResponse::new(String::from("1.1"), HttpStatus::NotFound, None, None));
+        Running::start(server).get(path)
+    };
+
+    // by default routes are case sensitive
+    assert!(fetch(false, "/Users").starts_with("HTTP/1.1 404"));
+    assert!(fetch(true, "/Users").starts_with("HTTP/1.1 200"));
+    assert!(fetch(true, "/users").starts_with("HTTP/1.1 200"));
+}
+
+#[test]
+fn no_cache_only_when_enabled() {
+    let fetch = |no_cache: bool| {
+        let mut server = common::server();
+        server.no_cache(no_cache);
+        server.get("/now".to_owned(), |_: &HttpRequest| HttpResponse::default());
+        Running::start(server).get("/now")
+    };
+
+    assert!(!fetch(false).contains("Cache-Control"));
+    assert!(fetch(true).contains("Cache-Control: no-store\r\n"));
+}
+
+#[test]
+fn cors_headers_for_allowed_origins() {
+    let mut server = common::server();
+    server.cors(Cors {
+        allowed_origins: vec![String::from("https://app.example")],
+        allowed_methods: vec![String::from("GET"), String::from("POST")],
+        ..Default::default()
+    });
+    server.get("/users".to_owned(), |_: &HttpRequest| {
+        HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, None)
+    });
+    let running = Running::start(server);
+    let get = |origin: &str| {
+        running.send(&format!("GET /users HTTP/1.1\r\nHost: localhost\r\n{origin}\r\n"))
+    };
+
+    // a simple cross origin GET from an allowed origin can be read by the browser
+    let response = get("Origin: https://app.example\r\n");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains("Access-Control-Allow-Origin: https://app.example\r\n"));
+    assert!(response.contains("Vary: Origin\r\n"));
+    // other origins and same origin requests get no CORS headers
+    assert!(!get("Origin: https://evil.example\r\n").contains("Access-Control-Allow-Origin"));
+    assert!(!get("").contains("Access-Control-Allow-Origin"));
+}
+
+#[test]
+fn auto_head_keeps_get_headers() {
+    let mut server = common::server();
+    server.auto_head(true);
+    server.get("/page".to_owned(), |_: &HttpRequest| {
+        let mut resp = HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, Some(HttpData::Bytes(b"<h1>hi</h1>".to_vec())));
+        resp.set_header("Content-Type", "text/html");
+        resp
+    });
+    let running = Running::start(server);
+    let send = |method: &str| {
+        let response = running.request(method, "/page");
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        // the Date header may differ between the two responses
+        let head: Vec<String> = head.lines().filter(|line| !line.starts_with("Date:")).map(String::from).collect();
+        (head, body.to_owned())
+    };
+
+    let (get_head, get_body) = send("GET");
+    let (head_head, head_body) = send("HEAD");
+    assert_eq!(get_body, "<h1>hi</h1>");
+    assert!(get_head.contains(&String::from("Content-Length: 11")));
+    assert_eq!(head_head, get_head);
+    assert_eq!(head_body, "");
+}
+
+#[test]
+fn trace_is_refused_with_routed_methods() {
+    let mut server = common::server();
+    server.get("/users".to_owned(), |_: &HttpRequest| HttpResponse::default());
+    server.host("api.example.com").post("/users".to_owned(), |_: &HttpRequest| HttpResponse::default());
+    let running = Running::start(server);
+
+    let response = running.send("TRACE /users HTTP/1.1\r\nHost: api.example.com:8443\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 405"));
+    // the Allow header lists the methods routed for the path and host
+    assert!(response.contains("Allow: GET, POST\r\n"));
+}
+
+#[test]
+fn connect_handler_takes_over_connection() {
+    let mut server = common::server();
+    server.connect(Box::new(|target: &str, _headers: &[(String, String)], mut client: TcpStream| {
+        let Ok(mut upstream) = TcpStream::connect(target) else {
+            let _ = client.write_all(b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n");
+            return;
+        };
+        let _ = client.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n");
+        let (mut client_read, mut upstream_write) = (client.try_clone().unwrap(), upstream.try_clone().unwrap());
+        thread::spawn(move || io::copy(&mut client_read, &mut upstream_write));
+        let _ = io::copy(&mut upstream, &mut client);
+    }));
+    let running = Running::start(server);
+
+    let response = running.send("CONNECT 127.0.0.1:1 HTTP/1.1\r\nHost: 127.0.0.1:1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 502"));
+}
+
+#[test]
+fn upload_reads_exactly_the_body() {
+    let mut server = common::server();
+    server.upload(HttpMethod::POST, "/echo".to_owned(), |_: &HttpRequest, body: &mut dyn Read| {
+        let mut received = Vec::new();
+        body.read_to_end(&mut received).unwrap();
+        HttpResponse::new(String::from("1.1"), HttpStatus::Ok, None, Some(HttpData::Bytes(received)))
+    });
+    let running = Running::start(server);
+
+    let response = running.send(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+    );
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("\r\n\r\nhello"));
+}
+
+#[test]
+fn panicking_error_handler_keeps_serving() {
+    let mut server = common::server();
+    server.on_error(HttpStatus::NotImplemented, |_: HttpResponse| panic!("broken error page"));
+    let running = Running::start(server);
+
+    for _ in 0..2 {
+        assert!(running.request("PURGE", "/").starts_with("HTTP/1.1 501"));
+    }
+}
+
+#[test]
+fn panicking_handler_is_answered_with_500() {
+    let mut server = common::server();
+    server.get("/panic".to_owned(), |_: &HttpRequest| -> HttpResponse { panic!("handler failed") });
+    let running = Running::start(server);
+
+    // the worker survives the panic and keeps serving
+    for _ in 0..2 {
+        assert!(running.get("/panic").starts_with("HTTP/1.1 500"));
+    }
+}
+
+#[test]
+fn panicking_handler_goes_through_error_handler() {
+    let mut server = common::server();
+    server.get("/panic".to_owned(), |_: &HttpRequest| -> HttpResponse { panic!("handler failed") });
+    server.on_error(HttpStatus::InternalServerError, |response: HttpResponse| HttpResponse {
+        data: Some(HttpData::Bytes(b"custom error page".to_vec())),
+        ..response
+    });
+    let running = Running::start(server);
+
+    let response = running.get("/panic");
+    assert!(response.starts_with("HTTP/1.1 500"), "{response}");
+    assert!(response.ends_with("\r\n\r\ncustom error page"), "{response}");
+}
+
+#[test]
+fn capture_raw_keeps_head_verbatim() {
+    let mut server = common::server();
+    server.capture_raw(&[HttpMethod::POST], "/raw".to_owned(), |_: &HttpRequest, head: &[u8]| {
+        HttpResponse {
+            data: Some(HttpData::Bytes(head.to_vec())),
+            ..Default::default()
+        }
+    });
+    let running = Running::start(server);
+
+    // spacing, case and bytes outside printable ascii are kept as sent
+    let head = "POST  /raw HTTP/1.1\r\nhost: localhost\r\nX-Note: 100% caf\u{e9}\r\nContent-Length: 4\r\n\r\n";
+    let response = running.send(&format!("{head}body"));
+    assert!(response.ends_with(&format!("\r\n\r\n{head}")));
+}
+
+#[test]
+fn handler_cannot_keep_the_connection_alive() {
+    let mut server = common::server();
+    server.get("/alive".to_owned(), |_: &HttpRequest| {
+        let mut resp = HttpResponse::default();
+        resp.set_header("Connection", "keep-alive");
+        resp
+    });
+    let running = Running::start(server);
+
+    let response = running.request("GET", "/alive");
+    assert!(response.contains("Connection: close\r\n"));
+    assert!(!response.contains("keep-alive"));
+}
+
+#[test]
+fn no_content_is_sent_without_body_or_length() {
+    let mut server = common::server();
+    server.delete("/users/42".to_owned(), |_: &HttpRequest| {
+        // a body set by mistake is dropped with its framing
+        let mut resp = HttpResponse::no_content();
+        resp.data = Some(HttpData::Bytes(b"gone".to_vec()));
+        resp
+    });
+    let running = Running::start(server);
+
+    let response = running.request("DELETE", "/users/42");
+    assert!(response.starts_with("HTTP/1.1 204"));
+    assert!(!response.contains("Content-Length"));
+    assert!(response.ends_with("\r\n\r\n"));
+}
+
+#[test]
+fn url_without_host_falls_back_to_the_local_address() {
+    let mut server = common::server();
+    server.get("/url".to_owned(), |request: &HttpRequest| {
+        let mut resp = HttpResponse::default();
+        let body = format!("{:?} {}", request.host(), request.url());
+        resp.data = Some(HttpData::Bytes(body.into_bytes()));
+        resp
+    });
+    let running = Running::start(server);
+
+    // HTTP/1.0 allows requests without Host, none is made up for them
+    let response = running.send("GET /url HTTP/1.0\r\n\r\n");
+    let expected = format!("\r\n\r\nNone http://{}/url", running.addr);
+    assert!(response.ends_with(&expected), "{response}");
+    // lines of the head may also end in a bare \n
+    let response = running.send("GET /url HTTP/1.0\nUser-Agent: test\n\n");
+    assert!(response.ends_with(&expected), "{response}");
+}
+
+#[test]
+fn peer_is_recorded_outside_the_headers() {
+    let mut server = common::server();
+    server.get("/peer".to_owned(), |request: &HttpRequest| {
+        let mut resp = HttpResponse::default();
+        let body = format!(
+            "{:?} {:?} {:?} {:?}",
+            request.peer_ip(),
+            request.real_ip(),
+            request.header("X-Real-IP"),
+            request.header("X-Peer-Addr")
+        );
+        resp.data = Some(HttpData::Bytes(body.into_bytes()));
+        resp
+    });
+    let running = Running::start(server);
+
+    // X-Peer-Addr sent by the client is an ordinary header and does not change the peer, real_ip
+    // falls back to the peer without a header being added for it
+    let response = running.send("GET /peer HTTP/1.1\r\nHost: localhost\r\nX-Peer-Addr: 10.0.0.2\r\n\r\n");
+    let expected = "\r\n\r\nSome(127.0.0.1) Some(127.0.0.1) None Some(\"10.0.0.2\")";
+    assert!(response.ends_with(expected), "{response}");
+}